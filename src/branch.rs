@@ -0,0 +1,122 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const MAIN: &str = "main";
+
+/// Named snapshots of a conversation: checkpoints to fork from, and branches to switch between.
+///
+/// The active branch's messages are the live conversation (and the session's `messages`);
+/// only the inactive branches are kept here.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Branches {
+    #[serde(default = "main_branch")]
+    pub current: String,
+    #[serde(default)]
+    pub branches: BTreeMap<String, Vec<serde_json::Value>>,
+    #[serde(default)]
+    pub checkpoints: BTreeMap<String, Vec<serde_json::Value>>,
+}
+
+fn main_branch() -> String {
+    MAIN.to_string()
+}
+
+impl Default for Branches {
+    fn default() -> Self {
+        Self { current: main_branch(), branches: BTreeMap::new(), checkpoints: BTreeMap::new() }
+    }
+}
+
+fn turns(messages: &[serde_json::Value]) -> usize {
+    messages.iter().filter(|m| m["role"] == "user").count()
+}
+
+impl Branches {
+    /// Nothing beyond the implicit main branch, so there is nothing to save.
+    pub fn is_empty(&self) -> bool {
+        self.current == MAIN && self.branches.is_empty() && self.checkpoints.is_empty()
+    }
+
+    /// Snapshot `conversation` as `name`; returns whether an older checkpoint was replaced.
+    pub fn checkpoint(&mut self, name: &str, conversation: &[serde_json::Value]) -> bool {
+        self.checkpoints.insert(name.to_string(), conversation.to_vec()).is_some()
+    }
+
+    /// Start branch `name` from checkpoint `from` (or the current point) and make it active.
+    pub fn branch(&mut self, name: &str, from: Option<&str>, conversation: &mut Vec<serde_json::Value>) -> Result<()> {
+        if name == self.current || self.branches.contains_key(name) {
+            bail!("Branch '{}' already exists; use /switch {}", name, name);
+        }
+        let start = match from {
+            Some(cp) => match self.checkpoints.get(cp) {
+                Some(messages) => messages.clone(),
+                None => bail!("No checkpoint named '{}' (see /branches)", cp),
+            },
+            None => conversation.clone(),
+        };
+        self.branches.insert(self.current.clone(), std::mem::replace(conversation, start));
+        self.current = name.to_string();
+        Ok(())
+    }
+
+    /// Make branch `name` active, keeping the current one.
+    pub fn switch(&mut self, name: &str, conversation: &mut Vec<serde_json::Value>) -> Result<()> {
+        if name == self.current {
+            bail!("Already on branch '{}'", name);
+        }
+        let Some(target) = self.branches.remove(name) else {
+            bail!("No branch named '{}' (see /branches)", name);
+        };
+        self.branches.insert(self.current.clone(), std::mem::replace(conversation, target));
+        self.current = name.to_string();
+        Ok(())
+    }
+
+    /// One line per branch and checkpoint, marking the active branch.
+    pub fn describe(&self, conversation: &[serde_json::Value]) -> Vec<String> {
+        let mut all: BTreeMap<&str, usize> = self.branches.iter().map(|(n, m)| (n.as_str(), turns(m))).collect();
+        all.insert(&self.current, turns(conversation));
+        let mut lines: Vec<String> = all
+            .into_iter()
+            .map(|(name, n)| {
+                let marker = if name == self.current { "*" } else { " " };
+                format!("{} {} ({} turns)", marker, name, n)
+            })
+            .collect();
+        for (name, messages) in &self.checkpoints {
+            lines.push(format!("  checkpoint {} ({} turns)", name, turns(messages)));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user(text: &str) -> serde_json::Value {
+        json!({"role": "user", "content": text})
+    }
+
+    #[test]
+    fn forks_from_a_checkpoint_and_switches_back() {
+        let mut branches = Branches::default();
+        let mut conversation = vec![user("one")];
+        branches.checkpoint("start", &conversation);
+        conversation.push(user("two"));
+
+        branches.branch("alt", Some("start"), &mut conversation).unwrap();
+        assert_eq!(conversation, vec![user("one")]);
+        assert_eq!(branches.current, "alt");
+
+        branches.switch(MAIN, &mut conversation).unwrap();
+        assert_eq!(conversation, vec![user("one"), user("two")]);
+        assert_eq!(branches.describe(&conversation), ["  alt (1 turns)", "* main (2 turns)", "  checkpoint start (1 turns)"]);
+
+        assert!(branches.branch("alt", None, &mut conversation).is_err());
+        assert!(branches.switch("missing", &mut conversation).is_err());
+        assert!(branches.branch("x", Some("missing"), &mut conversation).is_err());
+    }
+}
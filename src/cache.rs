@@ -0,0 +1,65 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// In-memory cache of API results for the current run, keyed by the exact request sent.
+///
+/// The key covers the URL (deployment and api-version) and the full request body (messages and
+/// parameters), so only byte-identical requests are answered from the cache.
+#[derive(Debug)]
+pub struct RequestCache<T> {
+    entries: Mutex<HashMap<String, T>>,
+    hits: AtomicUsize,
+}
+
+impl<T: Clone> RequestCache<T> {
+    pub fn new() -> Self {
+        Self { entries: Mutex::new(HashMap::new()), hits: AtomicUsize::new(0) }
+    }
+
+    pub fn get(&self, key: &str) -> Option<T> {
+        let hit = self.entries.lock().unwrap().get(key).cloned();
+        if hit.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        hit
+    }
+
+    pub fn insert(&self, key: String, value: T) {
+        self.entries.lock().unwrap().insert(key, value);
+    }
+
+    /// Requests answered from the cache so far.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+/// Cache key for a request body sent to `url`.
+pub fn request_key(url: &str, request: &impl Serialize) -> String {
+    format!("{}\n{}", url, serde_json::to_string(request).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reuses_only_identical_requests() {
+        let cache = RequestCache::new();
+        let a = request_key("u", &json!({"messages": [{"role": "user", "content": "hi"}], "temperature": 0.7}));
+        let b = request_key("u", &json!({"messages": [{"role": "user", "content": "hi"}], "temperature": 0.2}));
+        cache.insert(a.clone(), "first".to_string());
+
+        assert_eq!(cache.get(&a).as_deref(), Some("first"));
+        assert_eq!(cache.get(&b), None);
+        assert_eq!(cache.get(&request_key("other", &json!({}))), None);
+        assert_eq!(cache.hits(), 1);
+    }
+}
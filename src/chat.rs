@@ -0,0 +1,882 @@
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::cache::{self, RequestCache};
+use crate::errors;
+use crate::ratelimit::{self, RateLimiter};
+use crate::realtime::Realtime;
+use crate::responses;
+use crate::stats::LatencyStats;
+use crate::trace::Tracer;
+use crate::usage::{self, Usage};
+
+/// Reasoning effort hint accepted by o-series deployments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+}
+
+/// Which Azure OpenAI API chat requests go to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Api {
+    /// `/openai/deployments/{deployment}/chat/completions`
+    #[default]
+    Chat,
+    /// `/openai/responses`, with item-based streaming events
+    Responses,
+}
+
+/// What a deployment accepts in the request payload.
+/// Reasoning (o-series) models reject `temperature` and expect
+/// `max_completion_tokens` instead of `max_tokens`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    pub reasoning: bool,
+}
+
+impl ModelCapabilities {
+    /// Detect capabilities from the deployment/model name (e.g. `o1`, `o3-mini`, `o4-mini-prod`).
+    pub fn detect(model: &str) -> Self {
+        let lower = model.to_lowercase();
+        let reasoning = lower.split(['-', '_', '.']).any(|part| {
+            let mut chars = part.chars();
+            chars.next() == Some('o')
+                && chars.next().is_some_and(|c| c.is_ascii_digit())
+                && chars.all(|c| c.is_ascii_digit())
+        });
+        Self { reasoning }
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct ChatRequest {
+    pub messages: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<serde_json::Value>>, // OpenAI tool definitions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ChatMessage {
+    role: String,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseBasic {
+    choices: Vec<ChoiceBasic>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct ChoiceBasic {
+    message: ChatMessage,
+    #[serde(default)]
+    logprobs: Option<serde_json::Value>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+/// Text, reasoning, and usage gathered from stream chunks as they are printed.
+#[derive(Default)]
+struct StreamOutput {
+    text: String,
+    reasoning: String,
+    usage: Option<Usage>,
+    finish_reason: Option<String>,
+}
+
+impl StreamOutput {
+    fn into_reply(self) -> ChatReply {
+        ChatReply {
+            content: self.text,
+            reasoning: if self.reasoning.is_empty() { None } else { Some(self.reasoning) },
+            usage: self.usage,
+            choices: Vec::new(),
+            logprobs: Vec::new(),
+            finish_reason: self.finish_reason,
+        }
+    }
+}
+
+/// Assistant reply, with reasoning kept separate from the final answer.
+#[derive(Debug, Clone, Default)]
+pub struct ChatReply {
+    pub content: String,
+    pub reasoning: Option<String>,
+    pub usage: Option<Usage>,
+    /// Content of every returned choice when there is more than one (`content` is the first).
+    pub choices: Vec<String>,
+    /// Token logprobs of each returned choice, when requested.
+    pub logprobs: Vec<serde_json::Value>,
+    /// Why generation stopped (`stop`, `length`, ...), when the API said.
+    pub finish_reason: Option<String>,
+}
+
+impl ChatReply {
+    /// Cut off by the token limit rather than finished.
+    pub fn truncated(&self) -> bool {
+        self.finish_reason.as_deref() == Some("length")
+    }
+}
+
+pub struct ChatClient {
+    client: Client,
+    endpoint: String,
+    api_key: String,
+    model: String,
+    api_version: String,
+    api: Api,
+    caps: ModelCapabilities,
+    reasoning_effort: Option<ReasoningEffort>,
+    show_reasoning: bool,
+    n: u32,
+    seed: Option<u64>,
+    /// `Some(k)`: request logprobs with the top k alternatives per token (0 for none).
+    logprobs: Option<u8>,
+    /// Per-run caches of identical requests (batch runs only).
+    reply_cache: Option<RequestCache<ChatReply>>,
+    tools_cache: Option<RequestCache<serde_json::Value>>,
+    /// Request/response tracing for --verbose.
+    tracer: Option<Arc<Tracer>>,
+    /// Latency of chat requests answered by the API, for /stats.
+    latency: Mutex<LatencyStats>,
+    /// Client-side requests/minute and tokens/minute limits.
+    rate_limiter: Option<RateLimiter>,
+    /// How the model's tokenizer counts text, for estimating tokens against the limits.
+    chars_per_token: f32,
+    /// Persistent WebSocket connection for --realtime; chat turns go there instead of HTTPS.
+    realtime: Option<tokio::sync::Mutex<Realtime>>,
+}
+
+impl ChatClient {
+    pub fn new(endpoint: String, api_key: String, model: String, api_version: String) -> Self {
+        let caps = ModelCapabilities::detect(&model);
+        Self {
+            client: Client::new(),
+            endpoint,
+            api_key,
+            model,
+            api_version,
+            api: Api::Chat,
+            caps,
+            reasoning_effort: None,
+            show_reasoning: false,
+            n: 1,
+            seed: None,
+            logprobs: None,
+            reply_cache: None,
+            tools_cache: None,
+            tracer: None,
+            latency: Mutex::default(),
+            rate_limiter: None,
+            chars_per_token: usage::DEFAULT_CHARS_PER_TOKEN,
+            realtime: None,
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Send chat turns over one Realtime WebSocket connection; tool-loop requests still use HTTPS.
+    pub fn with_realtime(mut self, enabled: bool) -> Self {
+        self.realtime = enabled
+            .then(|| tokio::sync::Mutex::new(Realtime::new(&self.endpoint, &self.api_key, &self.model, &self.api_version)));
+        self
+    }
+
+    pub fn with_api(mut self, api: Api) -> Self {
+        self.api = api;
+        self
+    }
+
+    /// Override detected capabilities (e.g. for deployments with custom names).
+    pub fn with_capabilities(mut self, caps: ModelCapabilities) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    pub fn with_reasoning(mut self, effort: Option<ReasoningEffort>, show: bool) -> Self {
+        self.reasoning_effort = effort;
+        self.show_reasoning = show;
+        self
+    }
+
+    /// Number of choices to request per completion.
+    pub fn with_choices(mut self, n: u32) -> Self {
+        self.n = n.max(1);
+        self
+    }
+
+    /// Fixed seed for best-effort deterministic sampling, and token logprobs (`top_logprobs` implies `logprobs`).
+    pub fn with_sampling(mut self, seed: Option<u64>, logprobs: bool, top_logprobs: Option<u8>) -> Self {
+        self.seed = seed;
+        self.logprobs = top_logprobs.or(logprobs.then_some(0));
+        self
+    }
+
+    /// Whether token logprobs are requested.
+    pub fn logprobs(&self) -> bool {
+        self.logprobs.is_some()
+    }
+
+    /// Answer requests identical to an earlier one in this run from memory instead of calling the API.
+    pub fn with_cache(mut self, enabled: bool) -> Self {
+        self.reply_cache = enabled.then(RequestCache::new);
+        self.tools_cache = enabled.then(RequestCache::new);
+        self
+    }
+
+    /// Queue requests that would exceed these limits instead of sending them.
+    pub fn with_rate_limit(mut self, limiter: Option<RateLimiter>) -> Self {
+        self.rate_limiter = limiter;
+        self
+    }
+
+    /// Average characters per token of the model's tokenizer (see `usage::chars_per_token`).
+    pub fn with_chars_per_token(mut self, chars_per_token: f32) -> Self {
+        self.chars_per_token = chars_per_token;
+        self
+    }
+
+    pub fn with_tracer(mut self, tracer: Option<Tracer>) -> Self {
+        self.tracer = tracer.map(Arc::new);
+        self
+    }
+
+    /// Write `text` to the --verbose trace, if tracing is on.
+    pub fn trace(&self, text: &str) {
+        if let Some(tracer) = &self.tracer {
+            tracer.note(text);
+        }
+    }
+
+    /// Requests answered from the cache so far.
+    pub fn cache_hits(&self) -> usize {
+        self.reply_cache.as_ref().map_or(0, |c| c.hits()) + self.tools_cache.as_ref().map_or(0, |c| c.hits())
+    }
+
+    pub fn latency_stats(&self) -> LatencyStats {
+        self.latency.lock().unwrap().clone()
+    }
+
+    pub fn reset_stats(&self) {
+        *self.latency.lock().unwrap() = LatencyStats::default();
+    }
+
+    fn record_latency(&self, start: Instant) {
+        self.latency.lock().unwrap().record(start.elapsed());
+    }
+
+    /// Cached reply for `request`, without usage since no tokens were spent.
+    fn cached_reply(&self, key: &str) -> Option<ChatReply> {
+        let reply = self.reply_cache.as_ref()?.get(key)?;
+        Some(ChatReply { usage: None, ..reply })
+    }
+
+    /// Number of choices requested per turn.
+    pub fn choices(&self) -> u32 {
+        self.n
+    }
+
+    pub fn capabilities(&self) -> ModelCapabilities {
+        self.caps
+    }
+
+    /// Sampling/limit parameters this client sends, for recording in session metadata.
+    pub fn request_params(&self) -> BTreeMap<String, serde_json::Value> {
+        match serde_json::to_value(self.build_request(&[], None, false)) {
+            Ok(serde_json::Value::Object(map)) => map
+                .into_iter()
+                .filter(|(k, _)| k != "messages" && k != "stream")
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// URL and JSON body of `request` on `deployment`, in the format of the selected API.
+    fn prepare(&self, deployment: &str, request: &ChatRequest) -> (String, serde_json::Value) {
+        match self.api {
+            Api::Chat => (
+                self.deployment_url(deployment, "chat/completions"),
+                serde_json::to_value(request).unwrap_or_default(),
+            ),
+            Api::Responses => (
+                format!("{}/openai/responses?api-version={}", self.endpoint, self.api_version),
+                responses::request(deployment, request),
+            ),
+        }
+    }
+
+    /// A response body in the Chat Completions shape, whichever API produced it.
+    fn normalize(&self, v: serde_json::Value) -> Result<serde_json::Value> {
+        match self.api {
+            Api::Chat => Ok(v),
+            Api::Responses => responses::to_chat_completion(&v),
+        }
+    }
+
+    /// URL for an operation on any deployment, e.g. `embeddings` or `images/generations`.
+    pub fn deployment_url(&self, deployment: &str, operation: &str) -> String {
+        format!(
+            "{}/openai/deployments/{}/{}?api-version={}",
+            self.endpoint, deployment, operation, self.api_version
+        )
+    }
+
+    /// POST request with the Azure `api-key` header set.
+    fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        self.client.post(url).header("api-key", &self.api_key)
+    }
+
+    /// POST a JSON body (asking for SSE when `sse` is set), traced when --verbose is on.
+    /// Waits first when a client-side rate limit is configured and would be exceeded.
+    pub async fn post_json(&self, url: &str, body: &impl Serialize, sse: bool) -> reqwest::Result<reqwest::Response> {
+        let mut builder = self.post(url).header("Content-Type", "application/json").json(body);
+        if sse {
+            builder = builder.header("Accept", "text/event-stream");
+        }
+        self.send(builder, url, body, ratelimit::estimate_tokens(body, self.chars_per_token)).await
+    }
+
+    /// POST a multipart form (file uploads), rate-limited and traced like `post_json`.
+    /// `fields` describes the form in the trace, since the form itself cannot be printed.
+    pub async fn post_multipart(
+        &self,
+        url: &str,
+        form: reqwest::multipart::Form,
+        fields: &serde_json::Value,
+    ) -> reqwest::Result<reqwest::Response> {
+        self.send(self.post(url).multipart(form), url, fields, 0).await
+    }
+
+    async fn send(
+        &self,
+        builder: reqwest::RequestBuilder,
+        url: &str,
+        traced: &impl Serialize,
+        tokens: u32,
+    ) -> reqwest::Result<reqwest::Response> {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(tokens).await;
+        }
+        let Some(tracer) = &self.tracer else {
+            return builder.send().await;
+        };
+        tracer.request(url, &self.api_key, traced);
+        let start = Instant::now();
+        let result = builder.send().await;
+        tracer.response(&result, start.elapsed());
+        result
+    }
+
+    /// Read and parse a JSON response body, tracing it at -vv.
+    pub async fn read_json<T: DeserializeOwned>(&self, response: reqwest::Response) -> Result<T> {
+        let text = response.text().await?;
+        if let Some(tracer) = &self.tracer {
+            tracer.body(&text);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Embed a batch of inputs with an embeddings deployment; returns one vector per input, in order.
+    pub async fn embed(&self, deployment: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        let url = self.deployment_url(deployment, "embeddings");
+
+        let response = self
+            .post_json(&url, &serde_json::json!({ "input": inputs }), false)
+            .await
+            .context("Failed to send embeddings request to Azure OpenAI")?;
+
+        let response = errors::check(response).await?;
+
+        let v: serde_json::Value = self.read_json(response).await.context("Failed to parse embeddings response")?;
+        let mut data: Vec<(u64, Vec<f32>)> = v["data"]
+            .as_array()
+            .context("Invalid embeddings response")?
+            .iter()
+            .map(|d| {
+                let index = d["index"].as_u64().unwrap_or(0);
+                let embedding = serde_json::from_value(d["embedding"].clone()).unwrap_or_default();
+                (index, embedding)
+            })
+            .collect();
+        data.sort_by_key(|(i, _)| *i);
+        Ok(data.into_iter().map(|(_, e)| e).collect())
+    }
+
+    /// Build the request payload, adapted to what the deployment accepts.
+    pub fn build_request(
+        &self,
+        messages: &[serde_json::Value],
+        tools: Option<&[serde_json::Value]>,
+        stream: bool,
+    ) -> ChatRequest {
+        let (max_tokens, max_completion_tokens, temperature, reasoning_effort) = if self.caps.reasoning {
+            (None, Some(1000), None, self.reasoning_effort.map(|e| e.as_str().to_string()))
+        } else {
+            (Some(1000), None, Some(0.7), None)
+        };
+        ChatRequest {
+            messages: messages.to_vec(),
+            max_tokens,
+            max_completion_tokens,
+            temperature,
+            reasoning_effort,
+            n: (self.n > 1).then_some(self.n),
+            seed: self.seed,
+            // Reasoning deployments reject logprobs
+            logprobs: (self.logprobs.is_some() && !self.caps.reasoning).then_some(true),
+            top_logprobs: self.logprobs.filter(|k| *k > 0 && !self.caps.reasoning),
+            tools: tools.map(|t| t.to_vec()),
+            tool_choice: tools.map(|_| serde_json::json!({"type":"auto"})),
+            stream: Some(stream),
+            // Ask for a final usage chunk so streamed turns can be metered too
+            stream_options: stream.then(|| serde_json::json!({"include_usage": true})),
+        }
+    }
+
+    pub async fn send_message(&self, messages: &[serde_json::Value]) -> Result<ChatReply> {
+        if self.realtime.is_some() {
+            return self.realtime_turn(messages, false, &mut |_| {}).await;
+        }
+        let (url, body) = self.prepare(&self.model, &self.build_request(messages, None, false));
+        let key = cache::request_key(&url, &body);
+        if let Some(reply) = self.cached_reply(&key) {
+            return Ok(reply);
+        }
+        let start = Instant::now();
+
+        let response = self
+            .post_json(&url, &body, false)
+            .await
+            .context("Failed to send request to Azure OpenAI")?;
+
+        let response = errors::check(response).await?;
+
+        let v = self.read_json(response).await.context("Failed to parse response from Azure OpenAI")?;
+        let chat_response: ChatResponseBasic =
+            serde_json::from_value(self.normalize(v)?).context("Failed to parse response from Azure OpenAI")?;
+
+        let message = &chat_response
+            .choices
+            .first()
+            .context("No response choices available")?
+            .message;
+        let choices = if chat_response.choices.len() > 1 {
+            chat_response
+                .choices
+                .iter()
+                .map(|c| c.message.content.clone().unwrap_or_default())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let reply = ChatReply {
+            content: message.content.clone().unwrap_or_default(),
+            reasoning: message.reasoning_content.clone().filter(|r| !r.is_empty()),
+            usage: chat_response.usage,
+            choices,
+            logprobs: chat_response.choices.iter().filter_map(|c| c.logprobs.clone()).collect(),
+            finish_reason: chat_response.choices.first().and_then(|c| c.finish_reason.clone()),
+        };
+        self.record_latency(start);
+        if let Some(cache) = &self.reply_cache {
+            cache.insert(key, reply.clone());
+        }
+        Ok(reply)
+    }
+
+    /// Print the reply as it streams in, also passing each piece of its text to `on_text`.
+    pub async fn send_message_streaming(
+        &self,
+        messages: &[serde_json::Value],
+        on_text: &mut dyn FnMut(&str),
+    ) -> Result<ChatReply> {
+        if self.realtime.is_some() {
+            return self.realtime_turn(messages, true, on_text).await;
+        }
+        let (url, body) = self.prepare(&self.model, &self.build_request(messages, None, true));
+        let key = cache::request_key(&url, &body);
+        if let Some(reply) = self.cached_reply(&key) {
+            if let (true, Some(reasoning)) = (self.show_reasoning, &reply.reasoning) {
+                print!("\n💭 \x1b[2m{}\x1b[0m\n\n", reasoning);
+            }
+            println!("{}", reply.content);
+            on_text(&reply.content);
+            return Ok(reply);
+        }
+        let start = Instant::now();
+
+        let response = self
+            .post_json(&url, &body, true)
+            .await
+            .context("Failed to send request to Azure OpenAI (stream)")?;
+
+        let response = errors::check(response).await?;
+
+        // Stream Server-Sent Events: lines starting with 'data: '
+        let mut body_stream = response.bytes_stream();
+        let mut buffer = String::new();
+        let mut out = StreamOutput::default();
+        let mut done = false;
+
+        // Write prefix once; the caller prints the label.
+        while let Some(chunk) = body_stream.next().await {
+            let chunk = chunk.context("Failed reading stream chunk")?;
+            let s = String::from_utf8_lossy(&chunk);
+            buffer.push_str(&s);
+
+            // Process complete lines
+            while let Some(pos) = buffer.find('\n') {
+                let line = buffer[..pos].trim_end().to_string();
+                buffer.drain(..pos + 1);
+
+                if line.is_empty() {
+                    continue;
+                }
+                if let Some(tracer) = &self.tracer {
+                    tracer.sse(&line);
+                }
+
+                // Azure sends lines like: "data: {json}" and "data: [DONE]"
+                let data_prefix = "data:";
+                if let Some(rest) = line.strip_prefix(data_prefix) {
+                    let mut data = rest.trim().to_string();
+                    if data == "[DONE]" {
+                        done = true;
+                        break;
+                    }
+                    // Responses events end with `response.completed` instead; read their text as chat chunks
+                    if self.api == Api::Responses {
+                        done = responses::is_final(&data);
+                        match responses::to_chat_chunk(&data)? {
+                            Some(chunk) => data = chunk.to_string(),
+                            None => continue,
+                        }
+                    }
+                    if let Some(delta) = self.render_chunk(&data, &mut out, true) {
+                        on_text(&delta);
+                    }
+                    if done {
+                        break;
+                    }
+                }
+            }
+            if done {
+                break;
+            }
+        }
+
+        // Ensure newline after stream completes
+        println!();
+        let reply = out.into_reply();
+        self.record_latency(start);
+        if let Some(cache) = &self.reply_cache {
+            cache.insert(key, reply.clone());
+        }
+        Ok(reply)
+    }
+
+    /// Add one Chat Completions stream chunk to `out`, printing its text as it arrives when `print` is set.
+    /// Returns the reply text the chunk added.
+    fn render_chunk(&self, data: &str, out: &mut StreamOutput, print: bool) -> Option<String> {
+        if let Some(u) = extract_usage_from_stream_payload(data) {
+            out.usage = Some(u);
+        }
+        if let Some(reason) = extract_finish_reason_from_stream_payload(data) {
+            out.finish_reason = Some(reason);
+        }
+        if let Some(delta) = extract_reasoning_from_stream_payload(data) {
+            if print && self.show_reasoning {
+                if out.reasoning.is_empty() {
+                    print!("\n💭 ");
+                }
+                print!("\x1b[2m{}\x1b[0m", delta);
+                io::stdout().flush().ok();
+            }
+            out.reasoning.push_str(&delta);
+        }
+        if let Some(delta) = extract_delta_from_stream_payload(data) {
+            if print {
+                if self.show_reasoning && !out.reasoning.is_empty() && out.text.is_empty() {
+                    print!("\n\n");
+                }
+                print!("{}", delta);
+                io::stdout().flush().ok();
+            }
+            out.text.push_str(&delta);
+            return Some(delta);
+        }
+        None
+    }
+
+    /// One chat turn over the --realtime connection, rendered like an SSE stream when `print` is set.
+    async fn realtime_turn(
+        &self,
+        messages: &[serde_json::Value],
+        print: bool,
+        on_text: &mut dyn FnMut(&str),
+    ) -> Result<ChatReply> {
+        let realtime = self.realtime.as_ref().context("Realtime mode is off")?;
+        let request = self.build_request(messages, None, true);
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(ratelimit::estimate_tokens(&request, self.chars_per_token)).await;
+        }
+        let start = Instant::now();
+        let mut out = StreamOutput::default();
+        realtime
+            .lock()
+            .await
+            .turn(messages, &request, self.tracer.as_deref(), |data| {
+                if let Some(delta) = self.render_chunk(data, &mut out, print) {
+                    on_text(&delta);
+                }
+            })
+            .await?;
+        if print {
+            println!();
+        }
+        self.record_latency(start);
+        Ok(out.into_reply())
+    }
+
+    // Non-streaming call with tools enabled, returns full JSON value
+    pub async fn send_with_tools(
+        &self,
+        messages: &[serde_json::Value],
+        tools: &[serde_json::Value],
+    ) -> Result<serde_json::Value> {
+        let (url, body) = self.prepare(&self.model, &self.build_request(messages, Some(tools), false));
+        let key = cache::request_key(&url, &body);
+        if let Some(mut v) = self.tools_cache.as_ref().and_then(|c| c.get(&key)) {
+            // Tokens were only spent on the first call
+            if let Some(obj) = v.as_object_mut() {
+                obj.remove("usage");
+            }
+            return Ok(v);
+        }
+        let start = Instant::now();
+
+        let response = self
+            .post_json(&url, &body, false)
+            .await
+            .context("Failed to send request to Azure OpenAI (tools)")?;
+
+        let response = errors::check(response).await?;
+
+        let v = self.read_json(response).await.context("Failed to parse tools response")?;
+        let v = self.normalize(v)?;
+        self.record_latency(start);
+        if let Some(cache) = &self.tools_cache {
+            cache.insert(key, v.clone());
+        }
+        Ok(v)
+    }
+
+    /// One completion outside the conversation (MCP sampling requests), on `deployment`
+    /// (default: the chat deployment). Counted in /stats like chat requests.
+    pub async fn sample(
+        &self,
+        deployment: Option<&str>,
+        messages: &[serde_json::Value],
+        max_tokens: u32,
+        temperature: Option<f32>,
+    ) -> Result<serde_json::Value> {
+        let start = Instant::now();
+        let v = self.sample_untracked(deployment, messages, max_tokens, temperature).await?;
+        self.record_latency(start);
+        Ok(v)
+    }
+
+    /// Like `sample`, for the CLI's own housekeeping (session titles): left out of /stats.
+    pub async fn sample_untracked(
+        &self,
+        deployment: Option<&str>,
+        messages: &[serde_json::Value],
+        max_tokens: u32,
+        temperature: Option<f32>,
+    ) -> Result<serde_json::Value> {
+        let mut request = self.build_request(messages, None, false);
+        request.n = None;
+        request.logprobs = None;
+        request.top_logprobs = None;
+        if self.caps.reasoning {
+            request.max_completion_tokens = Some(max_tokens);
+        } else {
+            request.max_tokens = Some(max_tokens);
+            request.temperature = temperature.or(request.temperature);
+        }
+        let (url, body) = self.prepare(deployment.unwrap_or(&self.model), &request);
+
+        let response = self
+            .post_json(&url, &body, false)
+            .await
+            .context("Failed to send sampling request to Azure OpenAI")?;
+
+        let response = errors::check(response).await?;
+
+        let v = self.read_json(response).await.context("Failed to parse sampling response")?;
+        self.normalize(v)
+    }
+}
+
+/// Extract the incremental content delta from a single SSE JSON payload string.
+/// Returns Some(content) if choices[0].delta.content exists and is non-empty.
+pub fn extract_delta_from_stream_payload(data: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(data).ok()?;
+    let s = v
+        .get("choices")?
+        .get(0)?
+        .get("delta")?
+        .get("content")?
+        .as_str()?;
+    if s.is_empty() { None } else { Some(s.to_string()) }
+}
+
+/// Extract an incremental reasoning delta (`reasoning_content` or `reasoning`) from an SSE payload.
+pub fn extract_reasoning_from_stream_payload(data: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(data).ok()?;
+    let delta = v.get("choices")?.get(0)?.get("delta")?;
+    let s = delta
+        .get("reasoning_content")
+        .or_else(|| delta.get("reasoning"))?
+        .as_str()?;
+    if s.is_empty() { None } else { Some(s.to_string()) }
+}
+
+/// Extract the `usage` object sent in the final chunk when `stream_options.include_usage` is set.
+pub fn extract_usage_from_stream_payload(data: &str) -> Option<Usage> {
+    let v: serde_json::Value = serde_json::from_str(data).ok()?;
+    Usage::from_json(&v)
+}
+
+/// Extract `finish_reason` from the last content chunk of a stream (e.g. `"length"` when cut off).
+pub fn extract_finish_reason_from_stream_payload(data: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(data).ok()?;
+    Some(v.get("choices")?.get(0)?.get("finish_reason")?.as_str()?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_delta_content() {
+        let payload = r#"{"choices":[{"delta":{"content":"Hello"}}]}"#;
+        assert_eq!(extract_delta_from_stream_payload(payload), Some("Hello".to_string()));
+    }
+
+    #[test]
+    fn ignores_noncontent() {
+        let payload = r#"{"choices":[{"delta":{"role":"assistant"}}]}"#;
+        assert_eq!(extract_delta_from_stream_payload(payload), None);
+    }
+
+    #[test]
+    fn accumulates_sequence() {
+        let parts = vec![
+            r#"{"choices":[{"delta":{"content":"Hel"}}]}"#,
+            r#"{"choices":[{"delta":{"content":"lo"}}]}"#,
+            r#"{"choices":[{"delta":{"content":"!"}}]}"#,
+        ];
+        let mut s = String::new();
+        for p in parts {
+            if let Some(x) = extract_delta_from_stream_payload(p) { s.push_str(&x); }
+        }
+        assert_eq!(s, "Hello!");
+    }
+
+    #[test]
+    fn parses_reasoning_delta() {
+        let payload = r#"{"choices":[{"delta":{"reasoning_content":"Let me think"}}]}"#;
+        assert_eq!(extract_reasoning_from_stream_payload(payload), Some("Let me think".to_string()));
+        assert_eq!(extract_delta_from_stream_payload(payload), None);
+    }
+
+    #[test]
+    fn parses_stream_usage_chunk() {
+        let payload = r#"{"choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5,"total_tokens":15}}"#;
+        assert_eq!(extract_usage_from_stream_payload(payload).unwrap().total_tokens, 15);
+        assert_eq!(extract_delta_from_stream_payload(payload), None);
+    }
+
+    #[test]
+    fn parses_finish_reason() {
+        let payload = r#"{"choices":[{"delta":{},"finish_reason":"length"}]}"#;
+        assert_eq!(extract_finish_reason_from_stream_payload(payload).as_deref(), Some("length"));
+        assert_eq!(extract_finish_reason_from_stream_payload(r#"{"choices":[{"delta":{"content":"x"},"finish_reason":null}]}"#), None);
+    }
+
+    #[test]
+    fn detects_reasoning_models() {
+        assert!(ModelCapabilities::detect("o1").reasoning);
+        assert!(ModelCapabilities::detect("o3-mini").reasoning);
+        assert!(ModelCapabilities::detect("prod-o4-mini").reasoning);
+        assert!(!ModelCapabilities::detect("gpt-4o").reasoning);
+        assert!(!ModelCapabilities::detect("gpt-35-turbo").reasoning);
+    }
+
+    #[test]
+    fn reasoning_request_omits_temperature() {
+        let client = ChatClient::new("https://x".into(), "k".into(), "o3-mini".into(), "v".into())
+            .with_reasoning(Some(ReasoningEffort::High), false);
+        let v = serde_json::to_value(client.build_request(&[], None, false)).unwrap();
+        assert!(v.get("temperature").is_none());
+        assert!(v.get("max_tokens").is_none());
+        assert_eq!(v["max_completion_tokens"], 1000);
+        assert_eq!(v["reasoning_effort"], "high");
+
+        let client = ChatClient::new("https://x".into(), "k".into(), "gpt-4o".into(), "v".into())
+            .with_reasoning(Some(ReasoningEffort::High), false);
+        let v = serde_json::to_value(client.build_request(&[], None, false)).unwrap();
+        assert_eq!(v["max_tokens"], 1000);
+        assert!(v.get("reasoning_effort").is_none());
+    }
+}
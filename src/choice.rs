@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Select};
+use std::str::FromStr;
+
+use crate::chat::ChatReply;
+
+/// How to pick one reply when the API returns several choices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChoicePolicy {
+    /// Let the user pick interactively; falls back to `First` when not interactive.
+    Ask,
+    First,
+    Longest,
+    Shortest,
+    /// 1-based index; out-of-range indexes fall back to the last choice.
+    Index(usize),
+}
+
+impl FromStr for ChoicePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "ask" => Ok(ChoicePolicy::Ask),
+            "first" => Ok(ChoicePolicy::First),
+            "longest" => Ok(ChoicePolicy::Longest),
+            "shortest" => Ok(ChoicePolicy::Shortest),
+            other => match other.parse::<usize>() {
+                Ok(n) if n >= 1 => Ok(ChoicePolicy::Index(n)),
+                _ => Err(anyhow!("expected ask, first, longest, shortest, or a 1-based index; got '{}'", s)),
+            },
+        }
+    }
+}
+
+/// Pick a choice without user interaction; returns a 0-based index.
+pub fn pick(policy: ChoicePolicy, choices: &[String]) -> usize {
+    if choices.is_empty() {
+        return 0;
+    }
+    match policy {
+        ChoicePolicy::Ask | ChoicePolicy::First => 0,
+        ChoicePolicy::Longest => (0..choices.len()).max_by_key(|&i| choices[i].chars().count()).unwrap_or(0),
+        ChoicePolicy::Shortest => (0..choices.len()).min_by_key(|&i| choices[i].chars().count()).unwrap_or(0),
+        ChoicePolicy::Index(n) => (n - 1).min(choices.len() - 1),
+    }
+}
+
+/// Print all choices, then select one by policy (prompting when `Ask` and interactive).
+pub fn render_and_select(choices: &[String], policy: ChoicePolicy, interactive: bool) -> Result<usize> {
+    for (i, text) in choices.iter().enumerate() {
+        println!("── Choice {}/{} ──", i + 1, choices.len());
+        println!("{}\n", text);
+    }
+    let index = if policy == ChoicePolicy::Ask && interactive {
+        let items: Vec<String> = choices
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let preview: String = t.lines().next().unwrap_or("").chars().take(60).collect();
+                format!("{}: {}", i + 1, preview)
+            })
+            .collect();
+        Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Continue the conversation with which choice?")
+            .items(&items)
+            .default(0)
+            .interact()
+            .context("Failed to read choice")?
+    } else {
+        pick(policy, choices)
+    };
+    println!("✔ Using choice {}", index + 1);
+    Ok(index)
+}
+
+/// JSON record of one turn: every candidate with its logprobs, and which one the conversation kept.
+pub fn turn_record(input: &str, reply: &ChatReply, selected: usize) -> serde_json::Value {
+    let contents = if reply.choices.is_empty() { std::slice::from_ref(&reply.content) } else { &reply.choices[..] };
+    let choices: Vec<serde_json::Value> = contents
+        .iter()
+        .enumerate()
+        .map(|(i, content)| {
+            let mut choice = serde_json::json!({"index": i, "content": content});
+            if let Some(logprobs) = reply.logprobs.get(i) {
+                choice["logprobs"] = logprobs.clone();
+            }
+            choice
+        })
+        .collect();
+    serde_json::json!({
+        "input": input,
+        "choices": choices,
+        "selected": selected,
+        "finish_reason": reply.finish_reason,
+        "usage": reply.usage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_by_policy() {
+        let choices = vec!["medium".to_string(), "a much longer one".to_string(), "s".to_string()];
+        assert_eq!(pick(ChoicePolicy::First, &choices), 0);
+        assert_eq!(pick(ChoicePolicy::Longest, &choices), 1);
+        assert_eq!(pick(ChoicePolicy::Shortest, &choices), 2);
+        assert_eq!(pick(ChoicePolicy::Index(2), &choices), 1);
+        assert_eq!(pick(ChoicePolicy::Index(9), &choices), 2);
+    }
+
+    #[test]
+    fn parses_policies() {
+        assert_eq!("longest".parse::<ChoicePolicy>().unwrap(), ChoicePolicy::Longest);
+        assert_eq!("3".parse::<ChoicePolicy>().unwrap(), ChoicePolicy::Index(3));
+        assert!("0".parse::<ChoicePolicy>().is_err());
+    }
+
+    #[test]
+    fn records_every_choice_with_logprobs() {
+        let reply = ChatReply {
+            content: "a".to_string(),
+            choices: vec!["a".to_string(), "b".to_string()],
+            logprobs: vec![serde_json::json!({"content": []}), serde_json::json!({"content": []})],
+            ..Default::default()
+        };
+        let record = turn_record("hi", &reply, 1);
+        assert_eq!(record["choices"][1]["content"], "b");
+        assert_eq!(record["choices"][1]["logprobs"], serde_json::json!({"content": []}));
+        assert_eq!(record["selected"], 1);
+
+        let single = ChatReply { content: "only".to_string(), ..Default::default() };
+        let record = turn_record("hi", &single, 0);
+        assert_eq!(record["choices"][0]["content"], "only");
+        assert!(record["choices"][0].get("logprobs").is_none());
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+
+/// The system clipboard, opened on first use and then kept open: on Linux the copied text is
+/// served by this process, so it stays pasteable only while the clipboard is alive.
+#[derive(Default)]
+pub struct Clipboard {
+    inner: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn copy(&mut self, text: &str) -> Result<()> {
+        let clipboard = match &mut self.inner {
+            Some(clipboard) => clipboard,
+            None => self.inner.insert(arboard::Clipboard::new().context("Could not open the system clipboard")?),
+        };
+        clipboard.set_text(text).context("Failed to copy to the clipboard")
+    }
+}
+
+/// A fenced code block from a Markdown reply.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    pub lang: String,
+    pub code: String,
+}
+
+/// Fenced (``` or ~~~) code blocks in `text`, in order. An unclosed block runs to the end.
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(String, String, CodeBlock)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match &mut open {
+            None => {
+                let Some(fence) = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) else {
+                    continue;
+                };
+                let len = trimmed.len() - trimmed.trim_start_matches(fence.chars().next().unwrap()).len();
+                let indent = line.len() - trimmed.len();
+                let lang = trimmed[len..].split_whitespace().next().unwrap_or("").to_string();
+                open = Some((trimmed[..len].to_string(), " ".repeat(indent), CodeBlock { lang, code: String::new() }));
+            }
+            Some((fence, indent, block)) => {
+                if trimmed.starts_with(fence.as_str()) && trimmed.trim_start_matches(fence.as_str()).trim().is_empty() {
+                    blocks.push(open.take().unwrap().2);
+                    continue;
+                }
+                block.code.push_str(line.strip_prefix(indent.as_str()).unwrap_or(trimmed));
+                block.code.push('\n');
+            }
+        }
+    }
+    blocks.extend(open.map(|(_, _, block)| block));
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_fenced_code_blocks() {
+        let text = "Try this:\n\n```rust\nfn main() {}\n```\n\nOr:\n  ~~~~\n  ls -la\n  ```\n  ~~~~\n\n```sh\necho open";
+        let blocks = code_blocks(text);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0], CodeBlock { lang: "rust".into(), code: "fn main() {}\n".into() });
+        assert_eq!(blocks[1].code, "ls -la\n```\n");
+        assert_eq!(blocks[2].code, "echo open\n");
+        assert!(code_blocks("no code here").is_empty());
+    }
+}
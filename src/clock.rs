@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use std::str::FromStr;
+
+/// Timezone used for the date/time line injected into the system prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+}
+
+impl FromStr for Clock {
+    type Err = anyhow::Error;
+
+    /// `local`, `UTC`, or a fixed offset such as `+02:00` / `-0530`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "local" | "" => Ok(Clock::Local),
+            "utc" | "z" => Ok(Clock::Utc),
+            other => parse_offset(other)
+                .map(Clock::Fixed)
+                .ok_or_else(|| anyhow!("Invalid timezone '{}': use local, UTC, or an offset like +02:00", s)),
+        }
+    }
+}
+
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let s = s.strip_prefix("utc").unwrap_or(s);
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    // Digits are sliced by byte below
+    if !rest.bytes().all(|b| b.is_ascii_digit() || b == b':') {
+        return None;
+    }
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    let (h, m) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse::<i32>().ok()?),
+        _ => return None,
+    };
+    if h > 14 || m > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60))
+}
+
+impl Clock {
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        match self {
+            Clock::Local => Local::now().fixed_offset(),
+            Clock::Utc => Utc::now().fixed_offset(),
+            Clock::Fixed(offset) => Utc::now().with_timezone(offset),
+        }
+    }
+}
+
+/// The line added to the system prompt, e.g.
+/// "Current date and time: Thursday, 2025-01-02 09:30 (UTC+01:00)."
+pub fn describe(now: DateTime<FixedOffset>) -> String {
+    format!(
+        "Current date and time: {} (UTC{}).",
+        now.format("%A, %Y-%m-%d %H:%M"),
+        now.format("%:z")
+    )
+}
+
+/// Append the current date/time to the first system message, or add a system message if there is none.
+pub fn inject(messages: &mut Vec<serde_json::Value>, clock: Clock) {
+    let line = describe(clock.now());
+    match messages.iter_mut().find(|m| m["role"] == "system") {
+        Some(system) => {
+            let content = system["content"].as_str().unwrap_or("");
+            system["content"] = serde_json::Value::String(format!("{}\n\n{}", content, line));
+        }
+        None => messages.insert(0, serde_json::json!({"role": "system", "content": line})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parses_timezones() {
+        assert_eq!("local".parse::<Clock>().unwrap(), Clock::Local);
+        assert_eq!("UTC".parse::<Clock>().unwrap(), Clock::Utc);
+        assert_eq!("+05:30".parse::<Clock>().unwrap(), Clock::Fixed(FixedOffset::east_opt(19800).unwrap()));
+        assert_eq!("UTC-8".parse::<Clock>().unwrap(), Clock::Fixed(FixedOffset::west_opt(8 * 3600).unwrap()));
+        assert!("Europe/Paris".parse::<Clock>().is_err());
+    }
+
+    #[test]
+    fn rejects_non_ascii_offsets() {
+        assert!("+1é1".parse::<Clock>().is_err());
+        assert!("+０２:００".parse::<Clock>().is_err());
+    }
+
+    #[test]
+    fn describes_and_injects_the_time() {
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        let now = offset.with_ymd_and_hms(2025, 1, 2, 9, 30, 0).unwrap();
+        assert_eq!(describe(now), "Current date and time: Thursday, 2025-01-02 09:30 (UTC+01:00).");
+
+        let mut messages = vec![serde_json::json!({"role": "user", "content": "what day is it?"})];
+        inject(&mut messages, Clock::Utc);
+        assert_eq!(messages[0]["role"], "system");
+        assert!(messages[0]["content"].as_str().unwrap().contains("(UTC+00:00)"));
+    }
+}
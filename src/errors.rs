@@ -0,0 +1,183 @@
+use std::fmt;
+
+/// Broad category of an Azure OpenAI error, used to pick a hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    DeploymentNotFound,
+    InvalidKey,
+    AccessDenied,
+    WrongApiVersion,
+    RateLimited,
+    QuotaExceeded,
+    RegionUnavailable,
+    UnsupportedParameter,
+    ContextLengthExceeded,
+    ContentFilter,
+    ServerError,
+    Other,
+}
+
+/// A failed Azure OpenAI request with an actionable hint instead of a raw JSON body.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: u16,
+    pub code: Option<String>,
+    pub message: String,
+    pub kind: ErrorKind,
+}
+
+const DOCS_DEPLOYMENTS: &str = "https://learn.microsoft.com/azure/ai-services/openai/how-to/create-resource";
+const DOCS_API_VERSIONS: &str = "https://learn.microsoft.com/azure/ai-services/openai/api-version-deprecation";
+const DOCS_QUOTA: &str = "https://learn.microsoft.com/azure/ai-services/openai/quotas-limits";
+const DOCS_MODELS: &str = "https://learn.microsoft.com/azure/ai-services/openai/concepts/models";
+const DOCS_REASONING: &str = "https://learn.microsoft.com/azure/ai-services/openai/how-to/reasoning";
+const DOCS_CONTENT_FILTER: &str = "https://learn.microsoft.com/azure/ai-services/openai/concepts/content-filter";
+const DOCS_NETWORK: &str = "https://learn.microsoft.com/azure/ai-services/cognitive-services-virtual-networks";
+
+impl ErrorKind {
+    fn hint(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            ErrorKind::DeploymentNotFound => Some((
+                "The deployment name does not exist on this resource. --model must be the deployment name from Azure AI Foundry, not the model name. New deployments can take a few minutes to become available.",
+                DOCS_DEPLOYMENTS,
+            )),
+            ErrorKind::InvalidKey => Some((
+                "The API key was rejected. Check --api-key / OPENAI_API_KEY and that --endpoint points to the resource the key belongs to.",
+                DOCS_DEPLOYMENTS,
+            )),
+            ErrorKind::AccessDenied => Some((
+                "The resource refused the request. It may be restricted to private endpoints or selected networks.",
+                DOCS_NETWORK,
+            )),
+            ErrorKind::WrongApiVersion => Some((
+                "The endpoint path or --api-version is not recognized. Check the endpoint is just https://<resource>.openai.azure.com and try a current api-version such as 2025-01-01-preview.",
+                DOCS_API_VERSIONS,
+            )),
+            ErrorKind::RateLimited => Some((
+                "The deployment's tokens-per-minute or requests-per-minute limit was hit. Wait and retry, or raise the deployment's rate limit.",
+                DOCS_QUOTA,
+            )),
+            ErrorKind::QuotaExceeded => Some((
+                "The subscription's quota for this model is exhausted. Request more quota or use another deployment.",
+                DOCS_QUOTA,
+            )),
+            ErrorKind::RegionUnavailable => Some((
+                "This model or api-version is not available in the resource's region. Deploy it in a supported region.",
+                DOCS_MODELS,
+            )),
+            ErrorKind::UnsupportedParameter => Some((
+                "The deployment rejected a request parameter. Reasoning (o-series) deployments need --reasoning-model if their name does not start with o1/o3/o4.",
+                DOCS_REASONING,
+            )),
+            ErrorKind::ContextLengthExceeded => Some((
+                "The conversation is longer than the model's context window. Type 'clear' to start over.",
+                DOCS_MODELS,
+            )),
+            ErrorKind::ContentFilter => Some((
+                "The prompt or completion was blocked by the resource's content filter.",
+                DOCS_CONTENT_FILTER,
+            )),
+            ErrorKind::ServerError => Some(("Azure OpenAI had a transient server error. Retry in a moment.", DOCS_QUOTA)),
+            ErrorKind::Other => None,
+        }
+    }
+}
+
+impl ApiError {
+    /// Classify an error response from its HTTP status and body.
+    pub fn from_body(status: u16, body: &str) -> Self {
+        let v: serde_json::Value = serde_json::from_str(body).unwrap_or(serde_json::Value::Null);
+        let err = &v["error"];
+        let code = err["code"]
+            .as_str()
+            .map(|s| s.to_string())
+            .or_else(|| err["code"].as_u64().map(|n| n.to_string()));
+        let message = err["message"]
+            .as_str()
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| body.trim().to_string());
+        let kind = classify(status, code.as_deref(), &message);
+        Self { status, code, message, kind }
+    }
+}
+
+fn classify(status: u16, code: Option<&str>, message: &str) -> ErrorKind {
+    let code = code.unwrap_or("");
+    let msg = message.to_lowercase();
+    match (status, code) {
+        (_, "DeploymentNotFound") => ErrorKind::DeploymentNotFound,
+        (_, "content_filter") | (_, "ResponsibleAIPolicyViolation") => ErrorKind::ContentFilter,
+        (_, "context_length_exceeded") => ErrorKind::ContextLengthExceeded,
+        (_, "unsupported_parameter") | (_, "unsupported_value") => ErrorKind::UnsupportedParameter,
+        (_, "insufficient_quota") => ErrorKind::QuotaExceeded,
+        _ if msg.contains("region") && (msg.contains("not available") || msg.contains("not supported")) => {
+            ErrorKind::RegionUnavailable
+        }
+        _ if msg.contains("api version") || msg.contains("api-version") => ErrorKind::WrongApiVersion,
+        (401, _) => ErrorKind::InvalidKey,
+        (403, _) => ErrorKind::AccessDenied,
+        (404, _) => ErrorKind::WrongApiVersion,
+        (429, _) if msg.contains("quota") && !msg.contains("rate limit") => ErrorKind::QuotaExceeded,
+        (429, _) => ErrorKind::RateLimited,
+        (500..=599, _) => ErrorKind::ServerError,
+        _ => ErrorKind::Other,
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) if code != &self.status.to_string() => {
+                write!(f, "API request failed ({} {}): {}", self.status, code, self.message)?
+            }
+            _ => write!(f, "API request failed ({}): {}", self.status, self.message)?,
+        }
+        if let Some((hint, docs)) = self.kind.hint() {
+            write!(f, "\n💡 {}\n📖 {}", hint, docs)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Pass a successful response through; turn any other into an `ApiError`.
+pub async fn check(response: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(ApiError::from_body(status.as_u16(), &body).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_common_azure_errors() {
+        let cases = [
+            (404, r#"{"error":{"code":"DeploymentNotFound","message":"The API deployment for this resource does not exist."}}"#, ErrorKind::DeploymentNotFound),
+            (401, r#"{"error":{"code":"401","message":"Access denied due to invalid subscription key or wrong API endpoint."}}"#, ErrorKind::InvalidKey),
+            (404, r#"{"error":{"code":"404","message":"Resource not found"}}"#, ErrorKind::WrongApiVersion),
+            (429, r#"{"error":{"code":"429","message":"Requests have exceeded token rate limit of your current tier."}}"#, ErrorKind::RateLimited),
+            (429, r#"{"error":{"code":"insufficient_quota","message":"You exceeded your current quota."}}"#, ErrorKind::QuotaExceeded),
+            (400, r#"{"error":{"code":"unsupported_parameter","message":"Unsupported parameter: 'max_tokens'"}}"#, ErrorKind::UnsupportedParameter),
+            (400, r#"{"error":{"code":"BadRequest","message":"The model is not available in region westus."}}"#, ErrorKind::RegionUnavailable),
+            (503, "Service Unavailable", ErrorKind::ServerError),
+        ];
+        for (status, body, kind) in cases {
+            assert_eq!(ApiError::from_body(status, body).kind, kind, "{}", body);
+        }
+    }
+
+    #[test]
+    fn renders_hint_and_docs() {
+        let e = ApiError::from_body(404, r#"{"error":{"code":"DeploymentNotFound","message":"nope"}}"#);
+        let s = e.to_string();
+        assert!(s.starts_with("API request failed (404 DeploymentNotFound): nope"));
+        assert!(s.contains("💡"));
+        assert!(s.contains(DOCS_DEPLOYMENTS));
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Result};
+
+/// Parse a Markdown transcript into chat messages.
+///
+/// Each `## User`, `## Assistant`, or `## System` heading (case-insensitive) starts a message;
+/// its body runs until the next such heading. Text before the first heading is ignored.
+/// Other headings (including deeper ones like `### Notes`) are kept as message content.
+pub fn parse_markdown(text: &str) -> Result<Vec<serde_json::Value>> {
+    let mut messages = Vec::new();
+    let mut current: Option<(&str, Vec<&str>)> = None;
+    let mut in_fence = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let role = if in_fence { None } else { heading_role(line) };
+        match role {
+            Some(role) => {
+                if let Some((r, body)) = current.take() {
+                    push_message(&mut messages, r, &body);
+                }
+                current = Some((role, Vec::new()));
+            }
+            None => {
+                if let Some((_, body)) = current.as_mut() {
+                    body.push(line);
+                }
+            }
+        }
+    }
+    if let Some((r, body)) = current.take() {
+        push_message(&mut messages, r, &body);
+    }
+
+    if !messages.iter().any(|m| m["role"] == "user") {
+        return Err(anyhow!("No '## User' sections found"));
+    }
+    Ok(messages)
+}
+
+fn heading_role(line: &str) -> Option<&'static str> {
+    let title = line.strip_prefix("## ")?.trim().to_lowercase();
+    match title.as_str() {
+        "user" => Some("user"),
+        "assistant" => Some("assistant"),
+        "system" => Some("system"),
+        _ => None,
+    }
+}
+
+fn push_message(messages: &mut Vec<serde_json::Value>, role: &str, body: &[&str]) {
+    let content = body.join("\n").trim().to_string();
+    if !content.is_empty() {
+        messages.push(serde_json::json!({"role": role, "content": content}));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_user_and_assistant_sections() {
+        let md = "# Notes\nignored\n\n## User\nHello\n\n## Assistant\nHi!\n```md\n## User\nnot a heading\n```\n\n## user\nBye";
+        let msgs = parse_markdown(md).unwrap();
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0], serde_json::json!({"role":"user","content":"Hello"}));
+        assert!(msgs[1]["content"].as_str().unwrap().contains("not a heading"));
+        assert_eq!(msgs[2]["content"], "Bye");
+    }
+
+    #[test]
+    fn requires_a_user_section() {
+        assert!(parse_markdown("## Assistant\nonly me").is_err());
+    }
+}
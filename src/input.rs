@@ -0,0 +1,66 @@
+use dialoguer::theme::{ColorfulTheme, Theme};
+use std::{
+    io::{self, BufRead, Write},
+    sync::mpsc as std_mpsc,
+    thread,
+};
+use tokio::sync::mpsc;
+
+/// Reads stdin lines on a background thread so input can be typed while a reply is streaming.
+///
+/// The thread reads one line per request. That keeps it off the terminal while other prompts
+/// (such as the choice picker) are in use: only request a line when nothing else reads stdin.
+pub struct LineReader {
+    requests: std_mpsc::Sender<()>,
+    lines: mpsc::UnboundedReceiver<Option<String>>,
+    /// A line has been requested and not yet received.
+    outstanding: bool,
+}
+
+impl LineReader {
+    pub fn spawn() -> Self {
+        let (requests, wanted) = std_mpsc::channel::<()>();
+        let (tx, lines) = mpsc::unbounded_channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            while wanted.recv().is_ok() {
+                let mut line = String::new();
+                let read = match stdin.lock().read_line(&mut line) {
+                    Ok(0) | Err(_) => None,
+                    Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+                };
+                let eof = read.is_none();
+                if tx.send(read).is_err() || eof {
+                    break;
+                }
+            }
+        });
+        Self { requests, lines, outstanding: false }
+    }
+
+    /// Ask the thread for the next line, unless a request is already pending.
+    pub fn request(&mut self) {
+        if !self.outstanding && self.requests.send(()).is_ok() {
+            self.outstanding = true;
+        }
+    }
+
+    /// Wait for the requested line. `None` means stdin was closed.
+    pub async fn recv(&mut self) -> Option<String> {
+        self.request();
+        let line = self.lines.recv().await.flatten();
+        self.outstanding = false;
+        line
+    }
+}
+
+/// Print the input prompt the way dialoguer's `Input` renders it.
+pub fn print_prompt(prompt: &str) {
+    let mut s = String::new();
+    if ColorfulTheme::default().format_input_prompt(&mut s, prompt, None).is_ok() {
+        print!("{}", s);
+    } else {
+        print!("{}: ", prompt);
+    }
+    io::stdout().flush().ok();
+}
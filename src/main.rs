@@ -1,15 +1,51 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use dialoguer::{theme::ColorfulTheme, Input};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
 use std::{
+    collections::VecDeque,
     env,
-    io::{self, Write},
+    io::{self, IsTerminal},
+    path::PathBuf,
 };
-use futures_util::StreamExt;
+mod branch;
+mod cache;
+mod chat;
+mod choice;
+mod clipboard;
+mod clock;
+mod errors;
+mod import;
+mod input;
 mod mcp;
+mod media;
+mod net;
+mod rag;
+mod ratelimit;
+mod realtime;
+mod repl;
+mod responses;
+mod script;
+mod secrets;
+mod session;
+mod settings;
+mod stats;
+mod tee;
+mod trace;
+mod ui;
+mod usage;
+mod wizard;
+use chat::{Api, ChatClient, ModelCapabilities, ReasoningEffort};
+use choice::ChoicePolicy;
+use clock::Clock;
 use mcp::{config::McpConfig, host::McpHost};
+use repl::{Flow, RagOptions, Repl, ReplOptions};
+use session::{Session, SessionEnv, SessionMcpServer};
+use settings::Settings;
+
+const DEFAULT_API_VERSION: &str = "2025-01-01-preview";
+/// The Responses API needs a newer version than Chat Completions; used when none is chosen.
+const RESPONSES_API_VERSION: &str = "2025-04-01-preview";
+/// api-version of the Realtime endpoint, used with --realtime when none is chosen.
+const REALTIME_API_VERSION: &str = "2024-10-01-preview";
 
 #[derive(Parser)]
 #[command(name = "rust-openai-chat")]
@@ -37,11 +73,19 @@ struct Cli {
     #[arg(
         long,
         env = "OPENAI_API_VERSION",
-        default_value = "2025-01-01-preview",
+        default_value = DEFAULT_API_VERSION,
         hide_env_values = true
     )]
     api_version: String,
 
+    /// API for chat requests: chat (Chat Completions) or responses (the unified Responses API)
+    #[arg(long, value_enum, default_value = "chat")]
+    api: Api,
+
+    /// Keep one WebSocket connection to the Realtime endpoint for the whole chat (needs a realtime deployment)
+    #[arg(long)]
+    realtime: bool,
+
     /// Enable streaming responses (SSE). Set --stream=false to disable.
     #[arg(long, default_value_t = true, action = clap::ArgAction::Set, 
         help = "Enable streaming responses (SSE). Set --stream=false to disable.")]
@@ -50,403 +94,643 @@ struct Cli {
     /// Path to MCP configuration file (YAML). If provided, MCP tools can be used.
     #[arg(long, env = "MCP_CONFIG", hide_env_values = true)]
     mcp_config: Option<String>,
-}
 
-#[derive(Serialize)]
-struct ChatRequest {
-    messages: Vec<serde_json::Value>,
-    max_tokens: u32,
-    temperature: f32,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tools: Option<Vec<serde_json::Value>>, // OpenAI tool definitions
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tool_choice: Option<serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    stream: Option<bool>,
-}
+    /// Echo MCP server stderr to the terminal, tagged with the server name (it is always kept in a log)
+    #[arg(long)]
+    mcp_verbose: bool,
 
-#[derive(Serialize, Deserialize, Clone)]
-struct ChatMessage {
-    role: String,
-    content: String,
-}
+    /// Stop a turn when the model keeps calling MCP tools for more than this many rounds
+    #[arg(long, default_value_t = 10)]
+    max_tool_rounds: usize,
 
-#[derive(Deserialize)]
-struct ChatResponseBasic {
-    choices: Vec<ChoiceBasic>,
-}
+    /// Deny MCP tools tagged write or destructive, and untagged tools
+    #[arg(long)]
+    read_only: bool,
 
-#[derive(Deserialize)]
-struct ChoiceBasic {
-    message: ChatMessage,
-}
+    /// Reasoning effort for o-series deployments (low, medium, high). Can be set via OPENAI_REASONING_EFFORT
+    #[arg(long, env = "OPENAI_REASONING_EFFORT", value_enum, hide_env_values = true)]
+    reasoning_effort: Option<ReasoningEffort>,
 
-struct ChatClient {
-    client: Client,
-    endpoint: String,
-    api_key: String,
-    model: String,
-    api_version: String,
-}
+    /// Treat the deployment as a reasoning (o-series) model even if its name does not look like one.
+    #[arg(long)]
+    reasoning_model: bool,
 
-impl ChatClient {
-    fn new(endpoint: String, api_key: String, model: String, api_version: String) -> Self {
-        Self {
-            client: Client::new(),
-            endpoint,
-            api_key,
-            model,
-            api_version,
-        }
-    }
+    /// Display reasoning summaries (when returned) separately from the final answer.
+    #[arg(long)]
+    show_reasoning: bool,
 
-    async fn send_message(&self, messages: &[serde_json::Value]) -> Result<String> {
-        let url = format!(
-            "{}/openai/deployments/{}/chat/completions?api-version={}",
-            self.endpoint, self.model, self.api_version
-        );
-
-        let request = ChatRequest {
-            messages: messages.to_vec(),
-            max_tokens: 1000,
-            temperature: 0.7,
-            tools: None,
-            tool_choice: None,
-            stream: Some(false),
-        };
+    /// Number of choices to request per turn. With more than one, replies are not streamed and all are shown.
+    #[arg(long, default_value_t = 1)]
+    n: u32,
 
-        let response = self
-            .client
-            .post(&url)
-            .header("api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send request to Azure OpenAI")?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed: {}", error_text);
-        }
+    /// Which choice continues the conversation when several are returned: ask, first, longest, shortest, or a 1-based index.
+    /// `ask` falls back to `first` when not interactive.
+    #[arg(long, default_value = "ask")]
+    choice: ChoicePolicy,
 
-    let chat_response: ChatResponseBasic = response
-            .json()
-            .await
-            .context("Failed to parse response from Azure OpenAI")?;
-
-        Ok(chat_response
-            .choices
-            .first()
-            .context("No response choices available")?
-            .message
-            .content
-            .clone())
-    }
+    /// Send at most this many API requests per minute, queuing the rest
+    #[arg(long)]
+    requests_per_minute: Option<u32>,
 
-    async fn send_message_streaming(&self, messages: &[serde_json::Value]) -> Result<String> {
-        let url = format!(
-            "{}/openai/deployments/{}/chat/completions?api-version={}",
-            self.endpoint, self.model, self.api_version
-        );
-
-        let request = ChatRequest {
-            messages: messages.to_vec(),
-            max_tokens: 1000,
-            temperature: 0.7,
-            tools: None,
-            tool_choice: None,
-            stream: Some(true),
-        };
+    /// Keep estimated tokens (prompt + max_tokens) per minute under this, queuing requests that would exceed it
+    #[arg(long)]
+    tokens_per_minute: Option<u32>,
 
-    let response = self
-            .client
-            .post(&url)
-            .header("api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .header("Accept", "text/event-stream")
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send request to Azure OpenAI (stream)")?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed: {}", error_text);
-        }
+    /// Seed for best-effort reproducible sampling
+    #[arg(long)]
+    seed: Option<u64>,
 
-    // Stream Server-Sent Events: lines starting with 'data: '
-    let mut body_stream = response.bytes_stream();
-        let mut buffer = String::new();
-        let mut full_text = String::new();
-    let mut done = false;
-
-        // Write prefix once; the caller prints the label.
-        while let Some(chunk) = body_stream.next().await {
-            let chunk = chunk.context("Failed reading stream chunk")?;
-            let s = String::from_utf8_lossy(&chunk);
-            buffer.push_str(&s);
-
-            // Process complete lines
-        while let Some(pos) = buffer.find('\n') {
-                let line = buffer[..pos].trim_end().to_string();
-                buffer.drain(..pos + 1);
-
-                if line.is_empty() { continue; }
-
-                // Azure sends lines like: "data: {json}" and "data: [DONE]"
-                let data_prefix = "data:";
-                if let Some(rest) = line.strip_prefix(data_prefix) {
-                    let data = rest.trim();
-            if data == "[DONE]" { done = true; break; }
-
-                    if let Some(delta) = extract_delta_from_stream_payload(data) {
-                        print!("{}", delta);
-                        io::stdout().flush().ok();
-                        full_text.push_str(&delta);
-                    }
-                }
-            }
-            if done { break; }
-        }
+    /// Return the log probability of each output token (shown in `script --json` output)
+    #[arg(long)]
+    logprobs: bool,
 
-        // Ensure newline after stream completes
-        println!();
-        Ok(full_text)
-    }
+    /// Also return the N most likely alternatives per token, 0-20 (implies --logprobs)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=20))]
+    top_logprobs: Option<u8>,
 
-    // Non-streaming call with tools enabled, returns full JSON value
-    async fn send_with_tools(&self, messages: &[serde_json::Value], tools: &[serde_json::Value]) -> Result<serde_json::Value> {
-        let url = format!(
-            "{}/openai/deployments/{}/chat/completions?api-version={}",
-            self.endpoint, self.model, self.api_version
-        );
-
-        let request = ChatRequest {
-            messages: messages.to_vec(),
-            max_tokens: 1000,
-            temperature: 0.7,
-            tools: Some(tools.to_vec()),
-            tool_choice: Some(serde_json::json!({"type":"auto"})),
-            stream: Some(false),
-        };
+    /// Path to the CLI config file (YAML). Defaults to <config dir>/rust-openai-chat/config.yaml. Can be set via CHAT_CONFIG
+    #[arg(long, env = "CHAT_CONFIG", global = true, hide_env_values = true)]
+    config: Option<String>,
 
-        let response = self
-            .client
-            .post(&url)
-            .header("api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to send request to Azure OpenAI (tools)")?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed: {}", error_text);
-        }
+    /// Directory where chat sessions are saved. Can be set via CHAT_SESSIONS_DIR
+    #[arg(long, env = "CHAT_SESSIONS_DIR", global = true, hide_env_values = true)]
+    sessions_dir: Option<PathBuf>,
 
-        let v: serde_json::Value = response.json().await.context("Failed to parse tools response")?;
-        Ok(v)
-    }
+    /// Embeddings deployment used by `index` and `--rag`. Can be set via OPENAI_EMBEDDING_MODEL
+    #[arg(
+        long,
+        env = "OPENAI_EMBEDDING_MODEL",
+        default_value = "text-embedding-3-small",
+        global = true,
+        hide_env_values = true
+    )]
+    embedding_model: String,
+
+    /// Chat with local documents: retrieve relevant chunks from this index (name or path) for each question.
+    #[arg(long)]
+    rag: Option<String>,
+
+    /// Number of chunks retrieved per question in --rag mode.
+    #[arg(long, default_value_t = 4)]
+    rag_top_k: usize,
+
+    /// Trace API traffic: -v for request lines, status, latency, and rate-limit headers; -vv adds bodies and SSE lines
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Write the --verbose trace to this file (appending) instead of stderr; implies -v
+    #[arg(long, global = true)]
+    trace_file: Option<PathBuf>,
+
+    /// Add the current date, time, and timezone to the system prompt on every turn (or set `clock.inject` in the config)
+    #[arg(long)]
+    inject_datetime: bool,
+
+    /// Timezone for the injected date/time: local (default), UTC, or an offset like +02:00; implies --inject-datetime
+    #[arg(long)]
+    timezone: Option<Clock>,
+
+    /// Continue a saved session by id (see `sessions show`).
+    #[arg(long)]
+    resume: Option<String>,
+
+    /// Call the API for every request in a script run, even ones identical to an earlier request
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Do not save this chat as a session.
+    #[arg(long)]
+    no_save: bool,
+
+    /// Start without the banner, hints, and separator
+    #[arg(long)]
+    no_banner: bool,
+
+    /// Hide the tool-call spinner and the per-call summaries
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Append every assistant reply to this file (ANSI codes stripped)
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// With --output, write user inputs too, not just replies
+    #[arg(long, requires = "output")]
+    output_transcript: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Inspect saved chat sessions
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsCommand,
+    },
+    /// Create a session from a transcript written elsewhere
+    Import {
+        #[command(subcommand)]
+        format: ImportCommand,
+    },
+    /// MCP utilities
+    Mcp {
+        #[command(subcommand)]
+        action: McpCommand,
+    },
+    /// Chunk and embed local documents into an on-disk index for --rag
+    Index {
+        /// Directory to index (text files are read recursively)
+        dir: PathBuf,
+        /// Index name; defaults to the directory name
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Generate images with a DALL-E / gpt-image deployment
+    Image {
+        /// Text prompt describing the image
+        prompt: String,
+        /// Image size, e.g. 1024x1024, 1792x1024, 1024x1792
+        #[arg(long, default_value = "1024x1024")]
+        size: String,
+        /// Output file; with --n > 1 a number is appended (fox-1.png, fox-2.png, ...)
+        #[arg(long, default_value = "image.png")]
+        out: PathBuf,
+        /// Number of variations to generate
+        #[arg(long, default_value_t = 1)]
+        n: usize,
+        /// Image deployment name. Can be set via OPENAI_IMAGE_MODEL
+        #[arg(long, env = "OPENAI_IMAGE_MODEL", default_value = "dall-e-3", hide_env_values = true)]
+        deployment: String,
+    },
+    /// Transcribe an audio file with a Whisper deployment
+    Transcribe {
+        /// Audio file (wav, mp3, m4a, webm, ...)
+        file: PathBuf,
+        /// Spoken language as ISO-639-1 code (e.g. en); auto-detected when omitted
+        #[arg(long)]
+        language: Option<String>,
+        /// Write the transcript to this file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Whisper deployment name. Can be set via OPENAI_TRANSCRIBE_MODEL
+        #[arg(long, env = "OPENAI_TRANSCRIBE_MODEL", default_value = "whisper", hide_env_values = true)]
+        deployment: String,
+    },
+    /// Convert text to speech with a TTS deployment
+    Speak {
+        /// Text to speak
+        text: String,
+        /// Output audio file; the format follows the extension (mp3, opus, aac, flac, wav, pcm)
+        #[arg(long, default_value = "speech.mp3")]
+        out: PathBuf,
+        /// Voice (alloy, echo, fable, onyx, nova, shimmer)
+        #[arg(long, default_value = "alloy")]
+        voice: String,
+        /// TTS deployment name. Can be set via OPENAI_TTS_MODEL
+        #[arg(long, env = "OPENAI_TTS_MODEL", default_value = "tts", hide_env_values = true)]
+        deployment: String,
+    },
+    /// Run REPL inputs and commands from a file non-interactively
+    Script {
+        /// Command file: one input per line, `#` comments, `<<EOF` ... `EOF` for multi-line input
+        file: String,
+        /// On a failed turn, ask whether to continue instead of stopping the script
+        #[arg(long)]
+        pause_on_error: bool,
+        /// Print one JSON record per turn (all choices, logprobs, usage) instead of the transcript
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check configuration, proxy settings, and connectivity to the endpoint
+    Doctor,
+}
+
+#[derive(Subcommand)]
+enum ImportCommand {
+    /// Markdown with `## User` / `## Assistant` (and optional `## System`) sections
+    Markdown {
+        /// Markdown file to import
+        file: PathBuf,
+    },
 }
 
-/// Extract the incremental content delta from a single SSE JSON payload string.
-/// Returns Some(content) if choices[0].delta.content exists and is non-empty.
-fn extract_delta_from_stream_payload(data: &str) -> Option<String> {
-    let v: serde_json::Value = serde_json::from_str(data).ok()?;
-    let s = v
-        .get("choices")?
-        .get(0)?
-        .get("delta")?
-        .get("content")?
-        .as_str()?;
-    if s.is_empty() { None } else { Some(s.to_string()) }
+#[derive(Subcommand)]
+enum SessionsCommand {
+    /// List saved sessions with their titles, dates, turn counts, and token totals
+    List,
+    /// Print a saved session's transcript
+    Show {
+        /// Session id (file name without .json)
+        id: String,
+        /// Show the recorded environment (CLI version, model, api-version, MCP servers, parameters) instead
+        #[arg(long)]
+        env: bool,
+    },
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[derive(Subcommand)]
+enum McpCommand {
+    /// Run a built-in MCP server over stdio with deterministic test tools (echo, add, fail, sample, big)
+    FakeServer,
+    /// Print the JSON Schema of the MCP config file, for editor validation
+    Schema,
+}
+
+async fn run_index_command(client: &ChatClient, dir: PathBuf, name: Option<String>, embedding_model: &str) -> Result<()> {
+    let dir = dir.canonicalize().with_context(|| format!("Directory not found: {}", dir.display()))?;
+    let name = name
+        .or_else(|| dir.file_name().map(|n| n.to_string_lossy().to_string()))
+        .context("Cannot derive an index name; pass --name")?;
+    let store = rag::build_index(client, &dir, embedding_model).await?;
+    let path = rag::default_index_dir().join(format!("{}.json", name));
+    store.save(&path)?;
+    println!("✅ Indexed {} chunks from {} into {}", store.chunks.len(), dir.display(), path.display());
+    println!("Chat with it: rust-openai-chat --rag {}", name);
+    Ok(())
+}
 
-    #[test]
-    fn parses_delta_content() {
-        let payload = r#"{"choices":[{"delta":{"content":"Hello"}}]}"#;
-        assert_eq!(extract_delta_from_stream_payload(payload), Some("Hello".to_string()));
+async fn run_image_command(
+    client: &ChatClient,
+    prompt: &str,
+    size: &str,
+    out: &std::path::Path,
+    n: usize,
+    deployment: &str,
+) -> Result<()> {
+    // One request per variation: DALL-E 3 only accepts n=1
+    for i in 1..=n.max(1) {
+        eprint!("🎨 Generating image {}/{}...\r", i, n.max(1));
+        let bytes = media::generate_image(client, deployment, prompt, size).await?;
+        let path = media::numbered_path(out, i, n);
+        std::fs::write(&path, bytes).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("✅ Saved {}", path.display());
     }
+    Ok(())
+}
 
-    #[test]
-    fn ignores_noncontent() {
-        let payload = r#"{"choices":[{"delta":{"role":"assistant"}}]}"#;
-        assert_eq!(extract_delta_from_stream_payload(payload), None);
+fn run_import_command(dir: &std::path::Path, format: ImportCommand) -> Result<()> {
+    let ImportCommand::Markdown { file } = format;
+    let text = std::fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let mut messages = import::parse_markdown(&text).with_context(|| format!("Invalid transcript {}", file.display()))?;
+    if messages.first().is_none_or(|m| m["role"] != "system") {
+        messages.insert(0, serde_json::json!({"role":"system","content": repl::SYSTEM_PROMPT}));
     }
+    let env = SessionEnv {
+        cli_version: env!("CARGO_PKG_VERSION").to_string(),
+        imported_from: Some(file.display().to_string()),
+        ..Default::default()
+    };
+    let mut session = Session::new(env, messages);
+    session.save(dir)?;
+    println!("✅ Imported {} messages into session {}", session.messages.len(), session.id);
+    println!("Continue with: rust-openai-chat --resume {}", session.id);
+    Ok(())
+}
 
-    #[test]
-    fn accumulates_sequence() {
-        let parts = vec![
-            r#"{"choices":[{"delta":{"content":"Hel"}}]}"#,
-            r#"{"choices":[{"delta":{"content":"lo"}}]}"#,
-            r#"{"choices":[{"delta":{"content":"!"}}]}"#,
-        ];
-        let mut s = String::new();
-        for p in parts {
-            if let Some(x) = extract_delta_from_stream_payload(p) { s.push_str(&x); }
+fn run_sessions_command(dir: &std::path::Path, action: SessionsCommand) -> Result<()> {
+    match action {
+        SessionsCommand::List => print!("{}", session::format_list(&session::list(dir)?, None)),
+        SessionsCommand::Show { id, env } => {
+            let session = Session::load(dir, &id)?;
+            if env {
+                print!("{}", session::format_env(&session));
+            } else {
+                print!("{}", session::format_transcript(&session));
+            }
         }
-        assert_eq!(s, "Hello!");
+    }
+    Ok(())
+}
+
+/// Where a setting came from, for `doctor`.
+fn source_of(cli: bool, env_var: &str, config: bool) -> String {
+    if cli {
+        "command line".to_string()
+    } else if env::var(env_var).is_ok() {
+        env_var.to_string()
+    } else if config {
+        "config file".to_string()
+    } else {
+        "not set".to_string()
     }
 }
 
+async fn run_doctor_command(
+    config_path: &std::path::Path,
+    endpoint: Option<&str>,
+    sources: [(&str, String); 2],
+    api_key: bool,
+) -> Result<()> {
+    println!("🩺 rust-openai-chat {} doctor", env!("CARGO_PKG_VERSION"));
+    println!();
+    println!(
+        "Config file: {} ({})",
+        config_path.display(),
+        if config_path.exists() { "found" } else { "not found" }
+    );
+    println!("Endpoint:    {} ({})", endpoint.unwrap_or("-"), sources[0].1);
+    println!("API key:     {} ({})", if api_key { "set" } else { "missing" }, sources[1].1);
+    println!();
+
+    let proxy = net::ProxyEnv::detect();
+    println!("Proxy environment:");
+    if proxy.vars.is_empty() {
+        println!("  (no proxy variables set)");
+    }
+    for (name, value) in &proxy.vars {
+        println!("  {} = {}", name, net::redact(value));
+    }
+    let Some(endpoint) = endpoint else {
+        println!();
+        println!("❌ No endpoint configured; skipping connection checks.");
+        return Ok(());
+    };
+    if let Ok(url) = reqwest::Url::parse(endpoint) {
+        match proxy.proxy_for(&url) {
+            Some(p) => println!("  → requests to the endpoint go through {}", net::redact(p.as_str())),
+            None if url.host_str().is_some_and(|h| proxy.bypassed(h)) => {
+                println!("  → the endpoint matches NO_PROXY; connecting directly")
+            }
+            None => println!("  → connecting directly"),
+        }
+    }
+    println!();
+
+    println!("Connection to {}:", endpoint);
+    let checks = net::diagnose(endpoint, &proxy).await;
+    print!("{}", net::format_checks(&checks));
+    if checks.iter().all(|c| c.ok) {
+        println!("✅ The endpoint is reachable.");
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    let sessions_dir = cli.sessions_dir.clone().unwrap_or_else(session::default_sessions_dir);
+
+    let command = match cli.command.take() {
+        Some(Commands::Sessions { action }) => return run_sessions_command(&sessions_dir, action),
+        Some(Commands::Import { format }) => return run_import_command(&sessions_dir, format),
+        Some(Commands::Mcp { action: McpCommand::FakeServer }) => return mcp::fake::run().await,
+        Some(Commands::Mcp { action: McpCommand::Schema }) => {
+            println!("{}", McpConfig::schema());
+            return Ok(());
+        }
+        other => other,
+    };
 
-    // Read required configuration; error out if neither CLI args nor env vars provide them
-    let endpoint = cli.endpoint
-        .or_else(|| env::var("OPENAI_API_ENDPOINT").ok())
-        .context("Azure OpenAI endpoint is required. Provide it via --endpoint argument or OPENAI_API_ENDPOINT environment variable")?;
+    let config_path = cli.config.as_ref().map(PathBuf::from).unwrap_or_else(settings::default_config_path);
+    let mut settings = Settings::load(cli.config.as_deref())?;
+    let sources = [
+        ("endpoint", source_of(cli.endpoint.is_some(), "OPENAI_API_ENDPOINT", settings.endpoint.is_some())),
+        ("api key", source_of(cli.api_key.is_some(), "OPENAI_API_KEY", settings.api_key.is_some() || settings.api_key_in_keyring)),
+    ];
 
-    let api_key = cli.api_key
+    // Read required configuration: CLI args, then env vars, then the config file
+    let mut endpoint = cli.endpoint
+        .or_else(|| env::var("OPENAI_API_ENDPOINT").ok())
+        .or_else(|| settings.endpoint.clone());
+    let mut api_key = cli.api_key
         .or_else(|| env::var("OPENAI_API_KEY").ok())
-        .context("API key is required. Provide it via --api-key argument or OPENAI_API_KEY environment variable")?;
+        .or_else(|| settings.resolve_api_key());
+
+    if let Some(Commands::Doctor) = command {
+        return run_doctor_command(&config_path, endpoint.as_deref(), sources, api_key.is_some()).await;
+    }
+
+    // First run: offer a setup wizard instead of erroring out, when someone is at the terminal
+    if (endpoint.is_none() || api_key.is_none()) && io::stdin().is_terminal() && io::stdout().is_terminal() {
+        settings = wizard::run(settings, &config_path)?;
+        endpoint = endpoint.or_else(|| settings.endpoint.clone());
+        api_key = api_key.or_else(|| settings.resolve_api_key());
+    }
+
+    let endpoint = endpoint
+        .context("Azure OpenAI endpoint is required. Provide it via --endpoint argument, OPENAI_API_ENDPOINT environment variable, or the config file")?;
+    let api_key = api_key
+        .context("API key is required. Provide it via --api-key argument, OPENAI_API_KEY environment variable, or the config file")?;
 
     let model = if cli.model == "gpt-35-turbo" {
-        env::var("OPENAI_API_MODEL").unwrap_or_else(|_| cli.model)
+        env::var("OPENAI_API_MODEL").ok().or_else(|| settings.model.clone()).unwrap_or(cli.model)
     } else {
         cli.model
     };
+    let api_version = match (env::var("OPENAI_API_VERSION"), &settings.api_version) {
+        (Err(_), Some(v)) if cli.api_version == DEFAULT_API_VERSION => v.clone(),
+        (Err(_), None) if cli.api_version == DEFAULT_API_VERSION && cli.realtime => REALTIME_API_VERSION.to_string(),
+        (Err(_), None) if cli.api_version == DEFAULT_API_VERSION && cli.api == Api::Responses => {
+            RESPONSES_API_VERSION.to_string()
+        }
+        _ => cli.api_version.clone(),
+    };
+    if cli.api == Api::Responses && (cli.n > 1 || cli.seed.is_some() || cli.logprobs || cli.top_logprobs.is_some()) {
+        bail!("--n, --seed, --logprobs, and --top-logprobs are not supported with --api responses");
+    }
+    if cli.realtime && (cli.api == Api::Responses || cli.n > 1 || cli.seed.is_some() || cli.logprobs || cli.top_logprobs.is_some()) {
+        bail!("--realtime cannot be combined with --api responses, --n, --seed, --logprobs, or --top-logprobs");
+    }
+    // Tool-loop requests would go over HTTPS to the realtime deployment instead of the socket
+    if cli.realtime && cli.mcp_config.is_some() {
+        bail!("--realtime cannot be combined with --mcp-config (MCP_CONFIG): tool calls are not sent over the realtime connection");
+    }
+    if cli.realtime {
+        // Titles are requested over HTTPS, which realtime deployments do not serve
+        settings.sessions.auto_title = false;
+    }
+
+    let trace_level = if cli.trace_file.is_some() { cli.verbose.max(1) } else { cli.verbose };
+    let tracer = match trace_level {
+        0 => None,
+        level => Some(trace::Tracer::new(level, cli.trace_file.as_deref())?),
+    };
+
+    let mut caps = ModelCapabilities::detect(&model);
+    caps.reasoning |= cli.reasoning_model;
+    let chat_client = ChatClient::new(endpoint, api_key, model.clone(), api_version.clone())
+        .with_api(cli.api)
+        .with_realtime(cli.realtime)
+        .with_capabilities(caps)
+        .with_reasoning(cli.reasoning_effort, cli.show_reasoning)
+        .with_choices(cli.n)
+        .with_sampling(cli.seed, cli.logprobs, cli.top_logprobs)
+        .with_rate_limit(ratelimit::RateLimiter::new(
+            cli.requests_per_minute.or(settings.rate_limit.requests_per_minute),
+            cli.tokens_per_minute.or(settings.rate_limit.tokens_per_minute),
+        ))
+        .with_chars_per_token(usage::chars_per_token(&model, &settings))
+        .with_tracer(tracer)
+        // Scripted runs repeat requests (e.g. a prompt re-asked after `clear`); answer those from memory
+        .with_cache(matches!(command, Some(Commands::Script { .. })) && !cli.no_cache);
+
+    match command {
+        Some(Commands::Index { dir, name }) => {
+            return run_index_command(&chat_client, dir, name, &cli.embedding_model).await;
+        }
+        Some(Commands::Image { prompt, size, out, n, deployment }) => {
+            return run_image_command(&chat_client, &prompt, &size, &out, n, &deployment).await;
+        }
+        Some(Commands::Transcribe { file, language, out, deployment }) => {
+            let text = media::transcribe(&chat_client, &deployment, &file, language.as_deref()).await?;
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, &text).with_context(|| format!("Failed to write {}", path.display()))?;
+                    println!("✅ Saved transcript to {}", path.display());
+                }
+                None => println!("{}", text),
+            }
+            return Ok(());
+        }
+        Some(Commands::Speak { text, out, voice, deployment }) => {
+            let audio = media::speak(&chat_client, &deployment, &text, &voice, media::audio_format_for(&out)).await?;
+            std::fs::write(&out, audio).with_context(|| format!("Failed to write {}", out.display()))?;
+            println!("✅ Saved {}", out.display());
+            return Ok(());
+        }
+        _ => {}
+    }
 
-    let chat_client = ChatClient::new(endpoint, api_key, model, cli.api_version.clone());
+    let rag = match &cli.rag {
+        Some(index) => {
+            let store = rag::VectorStore::load(&rag::resolve_index_path(index))?;
+            if store.embedding_model != cli.embedding_model {
+                eprintln!(
+                    "⚠️ Index was built with embeddings deployment '{}', querying with '{}'",
+                    store.embedding_model, cli.embedding_model
+                );
+            }
+            Some(RagOptions { store, embedding_model: cli.embedding_model.clone(), top_k: cli.rag_top_k })
+        }
+        None => None,
+    };
 
     // Load MCP config and start servers (non-blocking best-effort)
     let mut mcp_host: Option<McpHost> = None;
     if let Some(cfg_path) = &cli.mcp_config {
         match McpConfig::load_from_path(cfg_path) {
             Ok(cfg) => {
-                match McpHost::from_config(cfg).await {
-                    Ok(host) => {
+                match McpHost::from_config(cfg, cli.mcp_verbose).await {
+                    Ok(mut host) => {
+                        host.policy.read_only = cli.read_only;
                         mcp_host = Some(host);
                         eprintln!("[MCP] Loaded servers and tools.");
                     }
                     Err(e) => eprintln!("[MCP] Failed to start servers: {}", e),
                 }
             }
-            Err(e) => eprintln!("[MCP] Failed to load config: {}", e),
+            Err(e) => eprintln!("[MCP] Failed to load config: {:#}", e),
         }
     }
-    let mut conversation: Vec<serde_json::Value> = vec![serde_json::json!({
-        "role":"system",
-        "content":"You are a helpful assistant."
-    })];
-
-    println!("🤖 Azure OpenAI Chat CLI");
-    println!("Type 'quit' or 'exit' to end the conversation.");
-    println!("Type 'clear' to clear the conversation history.");
-    println!("{}", "=".repeat(50));
 
-    loop {
-    // Read user input from prompt
-        let user_input: String = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt("You")
-            .interact_text()
-            .context("Failed to read user input")?;
-
-    // Handle special commands
-        match user_input.trim().to_lowercase().as_str() {
-            "quit" | "exit" => {
-                println!("👋 Goodbye!");
-                break;
-            }
-            "clear" => {
-                conversation.clear();
-                conversation.push(serde_json::json!({"role":"system","content":"You are a helpful assistant."}));
-                println!("🗑️ Conversation cleared!");
-                continue;
-            }
-            _ if user_input.trim().is_empty() => continue,
-            _ => {}
+    // Capture the environment so saved transcripts can be interpreted later
+    let mut params = chat_client.request_params();
+    params.insert("stream".to_string(), serde_json::json!(cli.stream && cli.n <= 1));
+    let session_env = SessionEnv {
+        cli_version: env!("CARGO_PKG_VERSION").to_string(),
+        model,
+        api_version,
+        mcp_servers: mcp_host
+            .as_ref()
+            .map(|h| h.servers().into_iter().map(|(name, info)| SessionMcpServer { name, info }).collect())
+            .unwrap_or_default(),
+        params,
+        imported_from: None,
+    };
+    let clock = if cli.inject_datetime || cli.timezone.is_some() || settings.clock.inject {
+        match (cli.timezone, &settings.clock.timezone) {
+            (Some(c), _) => Some(c),
+            (None, Some(tz)) => Some(tz.parse::<Clock>().context("Invalid clock.timezone in the config file")?),
+            (None, None) => Some(Clock::Local),
         }
+    } else {
+        None
+    };
+    let json = matches!(command, Some(Commands::Script { json: true, .. }));
+    let options = ReplOptions {
+        // Multiple choices arrive interleaved when streamed, and logprobs arrive per chunk; fetch them whole instead
+        stream: cli.stream && cli.n <= 1 && !chat_client.logprobs() && !json,
+        show_reasoning: cli.show_reasoning,
+        save: !cli.no_save,
+        sessions_dir,
+        choice_policy: cli.choice,
+        interactive: !matches!(command, Some(Commands::Script { .. })),
+        clock,
+        json,
+        quiet: cli.quiet,
+        max_tool_rounds: cli.max_tool_rounds,
+    };
+    let ui = ui::Ui::new(settings.greeting.clone(), !cli.no_banner);
+    let mut repl = Repl::new(chat_client, mcp_host, rag, settings, options, session_env);
+    if let Some(path) = &cli.output {
+        repl.tee = Some(tee::Tee::open(path, cli.output_transcript)?);
+    }
+    if let Some(id) = &cli.resume {
+        repl.resume(Session::load(&repl.options.sessions_dir, id)?);
+    }
 
-    // Append user message to the conversation history
-    conversation.push(serde_json::json!({"role":"user","content": user_input}));
+    if let Some(Commands::Script { file, pause_on_error, .. }) = command {
+        return script::run_script(&mut repl, &file, pause_on_error).await;
+    }
 
-    // Show a "thinking" indicator
-        print!("🤖 Assistant: ");
-        io::stdout().flush().unwrap();
-        if !cli.stream {
-            print!("thinking...\r");
-            io::stdout().flush().unwrap();
-        }
+    ui.print_banner(&repl.banner_info());
 
-    // Send request to Azure OpenAI (MVP: no tool-call loop yet)
-        let result = if cli.stream && mcp_host.is_none() {
-            chat_client.send_message_streaming(&conversation).await
-        } else if mcp_host.is_none() {
-            chat_client.send_message(&conversation).await
-        } else {
-            // With MCP enabled, run non-streaming tool-call loop
-            // Build tool definitions from MCP
-            let mut host = mcp_host.as_mut().unwrap();
-            let tools: Vec<serde_json::Value> = host.tools.values().map(|(_server, desc)| {
-                serde_json::json!({
-                    "type":"function",
-                    "function":{
-                        "name": desc.name,
-                        "description": desc.description.clone().unwrap_or_default(),
-                        "parameters": desc.input_schema
-                    }
-                })
-            }).collect();
-
-            let mut local_conv = conversation.clone();
-            let final_text = loop {
-                let resp = chat_client.send_with_tools(&local_conv, &tools).await?;
-                let choice = &resp["choices"][0]["message"];
-                // Append assistant message (may have tool_calls)
-                local_conv.push(choice.clone());
-                if let Some(tool_calls) = choice.get("tool_calls").and_then(|v| v.as_array()) {
-                    for tc in tool_calls {
-                        let id = tc["id"].as_str().unwrap_or_default();
-                        let func = &tc["function"];
-                        let name = func["name"].as_str().unwrap_or("");
-                        let args_str = func["arguments"].as_str().unwrap_or("{}");
-                        let args_json: serde_json::Value = serde_json::from_str(args_str).unwrap_or(serde_json::json!({"raw": args_str}));
-                        let tool_result = host.call(name, args_json).await.unwrap_or(serde_json::json!({"error":"tool call failed"}));
-                        local_conv.push(serde_json::json!({
-                            "role":"tool",
-                            "tool_call_id": id,
-                            "content": serde_json::to_string(&tool_result).unwrap_or("null".to_string())
-                        }));
-                    }
-                    // Continue loop to let model consume tool outputs
-                    continue;
-                } else {
-                    // No tool calls; return content
-                    let content = choice.get("content").and_then(|c| c.as_str()).unwrap_or("").to_string();
-                    break content;
+    // Lines typed while a reply is still arriving are queued and sent after it
+    let mut reader = input::LineReader::spawn();
+    let mut pending: VecDeque<String> = VecDeque::new();
+    let mut stdin_open = true;
+    loop {
+        let user_input = match pending.pop_front() {
+            Some(line) => {
+                match pending.len() {
+                    0 => input::print_prompt(&repl.prompt()),
+                    n => input::print_prompt(&format!("{} (pending: {})", repl.prompt(), n)),
                 }
-            };
-
-            // Update real conversation with latest assistant text
-            Ok(final_text)
+                println!("{}", line);
+                line
+            }
+            None if stdin_open => {
+                input::print_prompt(&repl.prompt());
+                match reader.recv().await {
+                    Some(line) => line,
+                    None => break,
+                }
+            }
+            None => break,
         };
 
-        match result {
-            Ok(response) => {
-                // For non-streaming mode: clear "thinking..." and print reply
-                if !cli.stream {
-                    print!("\r🤖 Assistant: {}\n", response);
+        let accept_input = stdin_open && !repl.prompts_during_turn();
+        let result = {
+            let turn = repl.handle_line(&user_input);
+            tokio::pin!(turn);
+            let mut listening = accept_input;
+            loop {
+                tokio::select! {
+                    result = &mut turn => break result,
+                    line = reader.recv(), if listening => match line {
+                        Some(line) if line.trim().is_empty() => {}
+                        Some(line) => {
+                            pending.push_back(line);
+                            println!("⏳ pending: {}", pending.len());
+                        }
+                        None => {
+                            listening = false;
+                            stdin_open = false;
+                        }
+                    },
                 }
-
-                // Append assistant reply to conversation history
-                conversation.push(serde_json::json!({"role":"assistant","content": response}));
             }
+        };
+        match result {
+            Ok(Flow::Quit) => break,
+            Ok(Flow::Continue) => {}
             Err(e) => {
-                println!("\r❌ Error: {}", e);
-                // On error, remove the last user message from history
-                conversation.pop();
+                println!("\r❌ Error: {}\n", e);
+                repl.diagnose_failure(&e).await;
             }
         }
-
-        println!();
     }
 
     Ok(())
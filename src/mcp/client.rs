@@ -1,16 +1,179 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Result};
 use serde_json::json;
 use serde::{Deserialize, Serialize};
-use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, process::{Child, ChildStdin, ChildStdout}};
-use tokio_util::codec::{FramedRead, LinesCodec};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+use crate::mcp::sampling::Sampler;
+
+/// Requests waiting for a response, keyed by JSON-RPC id.
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<serde_json::Value>>>>;
+
+/// Takes a request out of `Pending` however waiting for it ends: response, write error, timeout, or cancellation.
+struct PendingEntry {
+    pending: Pending,
+    id: u64,
+}
+
+impl Drop for PendingEntry {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
 
 #[derive(Debug)]
 pub struct McpClient {
     pub name: String,
+    #[allow(dead_code)] // held so the server process lives as long as the client
     child: Child,
     stdin: ChildStdin,
-    stdout: ChildStdout,
     id_counter: u64,
+    /// Server identity reported by `initialize`.
+    pub server_info: Option<McpServerInfo>,
+    pending: Pending,
+    /// Notifications and requests the server sent on its own.
+    incoming: mpsc::UnboundedReceiver<ServerMessage>,
+    /// Notifications received while waiting for a response, kept for `take_messages`.
+    deferred: Vec<ServerMessage>,
+    reader: JoinHandle<()>,
+    /// Give up on a request the server has not answered in this long (`timeout_secs`).
+    pub timeout: Option<Duration>,
+}
+
+/// A message the server sent unprompted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Notification { method: String, params: serde_json::Value },
+    Request { id: serde_json::Value, method: String, params: serde_json::Value },
+}
+
+/// How a line read from the server is routed.
+#[derive(Debug, PartialEq)]
+enum Incoming {
+    Response(u64, serde_json::Value),
+    Server(ServerMessage),
+    Invalid,
+}
+
+fn classify(v: serde_json::Value) -> Incoming {
+    match (v.get("method").and_then(|m| m.as_str()), v.get("id")) {
+        (Some(method), Some(id)) => Incoming::Server(ServerMessage::Request {
+            id: id.clone(),
+            method: method.to_string(),
+            params: v["params"].clone(),
+        }),
+        (Some(method), None) => Incoming::Server(ServerMessage::Notification {
+            method: method.to_string(),
+            params: v["params"].clone(),
+        }),
+        (None, Some(id)) => match id.as_u64() {
+            Some(id) => Incoming::Response(id, v),
+            None => Incoming::Invalid,
+        },
+        (None, None) => Incoming::Invalid,
+    }
+}
+
+/// One line of server output, read with a size limit.
+#[derive(Debug, PartialEq)]
+enum Line {
+    Text(String),
+    /// Longer than the limit; its bytes were read and dropped.
+    TooLong(usize),
+    Eof,
+}
+
+/// Read up to the next newline, keeping at most `max` bytes of it.
+async fn read_line(reader: &mut (impl AsyncBufRead + Unpin), max: Option<usize>) -> std::io::Result<Line> {
+    let mut buf = Vec::new();
+    let limit = max.map_or(u64::MAX, |m| m as u64 + 1);
+    let n = (&mut *reader).take(limit).read_until(b'\n', &mut buf).await?;
+    if n == 0 {
+        return Ok(Line::Eof);
+    }
+    if max.is_some_and(|m| buf.len() > m && buf.last() != Some(&b'\n')) {
+        // Drop the rest of the line without holding it in memory
+        let mut total = buf.len();
+        loop {
+            buf.clear();
+            let n = (&mut *reader).take(64 * 1024).read_until(b'\n', &mut buf).await?;
+            total += n;
+            if n == 0 || buf.last() == Some(&b'\n') {
+                break;
+            }
+        }
+        return Ok(Line::TooLong(total));
+    }
+    Ok(Line::Text(String::from_utf8_lossy(&buf).into_owned()))
+}
+
+/// Route every line from the server's stdout until it closes.
+async fn read_loop(
+    name: String,
+    stdout: impl AsyncRead + Unpin,
+    max_bytes: Option<usize>,
+    pending: Pending,
+    tx: mpsc::UnboundedSender<ServerMessage>,
+) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let line = match read_line(&mut reader, max_bytes).await {
+            Ok(Line::Text(line)) => line,
+            Ok(Line::TooLong(size)) => {
+                // The message cannot be matched to its request. With one request waiting it must be the answer;
+                // with several, the others are left to their own responses or timeouts.
+                let message = format!("server output of {} bytes exceeds max_output_bytes ({})", size, max_bytes.unwrap_or(0));
+                eprintln!("[MCP] {}: {}", name, message);
+                let mut pending = pending.lock().unwrap();
+                if pending.len() == 1 {
+                    for (_, waiter) in pending.drain() {
+                        let _ = waiter.send(json!({"error": {"code": -32000, "message": message}}));
+                    }
+                }
+                continue;
+            }
+            Ok(Line::Eof) | Err(_) => break,
+        };
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+            eprintln!("[MCP] {}: ignoring non-JSON output: {}", name, line);
+            continue;
+        };
+        match classify(v) {
+            Incoming::Response(id, v) => {
+                if let Some(waiter) = pending.lock().unwrap().remove(&id) {
+                    let _ = waiter.send(v);
+                }
+            }
+            Incoming::Server(msg) => {
+                let _ = tx.send(msg);
+            }
+            Incoming::Invalid => eprintln!("[MCP] {}: ignoring unexpected message: {}", name, line),
+        }
+    }
+    // Dropping the waiters fails any request still in flight
+    pending.lock().unwrap().clear();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerInfo {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub protocol_version: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,40 +185,52 @@ pub struct McpToolDescription {
 }
 
 impl McpClient {
-    pub fn new(name: String, child: Child, stdin: ChildStdin, stdout: ChildStdout) -> Self {
-        Self { name, child, stdin, stdout, id_counter: 0 }
+    /// `max_output_bytes`: the largest message accepted from the server.
+    pub fn new(name: String, child: Child, stdin: ChildStdin, stdout: ChildStdout, max_output_bytes: Option<usize>) -> Self {
+        let pending: Pending = Arc::default();
+        let (tx, incoming) = mpsc::unbounded_channel();
+        let reader = tokio::spawn(read_loop(name.clone(), stdout, max_output_bytes, pending.clone(), tx));
+        Self {
+            name,
+            child,
+            stdin,
+            id_counter: 0,
+            server_info: None,
+            pending,
+            incoming,
+            deferred: Vec::new(),
+            reader,
+            timeout: None,
+        }
     }
 
     fn next_id(&mut self) -> u64 { self.id_counter += 1; self.id_counter }
 
-    pub async fn initialize(&mut self) -> Result<()> {
+    /// `sampling`: offer `sampling/createMessage` to the server.
+    pub async fn initialize(&mut self, sampling: bool) -> Result<()> {
+        let capabilities = if sampling { json!({"sampling": {}}) } else { json!({}) };
         // Minimal MCP initialize over JSON-RPC
-        let id = self.next_id();
-        let req = json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "clientInfo": {"name": "rust-openai-chat", "version": env!("CARGO_PKG_VERSION")}
-            }
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": capabilities,
+                    "clientInfo": {"name": "rust-openai-chat", "version": env!("CARGO_PKG_VERSION")}
+                }),
+            )
+            .await?;
+        self.server_info = Some(McpServerInfo {
+            name: result["serverInfo"]["name"].as_str().unwrap_or(&self.name).to_string(),
+            version: result["serverInfo"]["version"].as_str().map(|s| s.to_string()),
+            protocol_version: result["protocolVersion"].as_str().map(|s| s.to_string()),
         });
-        self.send(req).await?;
-        let _resp = self.read().await?; // TODO: validate
-        Ok(())
+        self.send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await
     }
 
     pub async fn list_tools(&mut self) -> Result<Vec<McpToolDescription>> {
-        let id = self.next_id();
-        let req = json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "method": "tools/list",
-            "params": {}
-        });
-        self.send(req).await?;
-        let resp = self.read().await?;
-        let tools = resp["result"]["tools"].as_array()
+        let result = self.request("tools/list", json!({})).await?;
+        let tools = result["tools"].as_array()
             .ok_or_else(|| anyhow!("Invalid tools/list response"))?
             .iter()
             .map(|t| McpToolDescription {
@@ -67,16 +242,93 @@ impl McpClient {
         Ok(tools)
     }
 
-    pub async fn call_tool(&mut self, name: &str, args: serde_json::Value) -> Result<serde_json::Value> {
+    /// Call a tool; sampling requests the server makes meanwhile go to `sampler`.
+    pub async fn call_tool(
+        &mut self,
+        name: &str,
+        args: serde_json::Value,
+        sampler: Option<&Sampler<'_>>,
+    ) -> Result<serde_json::Value> {
+        self.request_serving("tools/call", json!({"name": name, "arguments": args}), sampler).await
+    }
+
+    /// Messages the server sent on its own since the last call.
+    pub fn take_messages(&mut self) -> Vec<ServerMessage> {
+        let mut out = std::mem::take(&mut self.deferred);
+        while let Ok(msg) = self.incoming.try_recv() {
+            out.push(msg);
+        }
+        out
+    }
+
+    /// Answer a request the server sent: `ping`, and `sampling/createMessage` when a sampler is given.
+    pub async fn serve(
+        &mut self,
+        id: serde_json::Value,
+        method: &str,
+        params: &serde_json::Value,
+        sampler: Option<&Sampler<'_>>,
+    ) -> Result<()> {
+        let result = match (method, sampler) {
+            ("ping", _) => Ok(json!({})),
+            ("sampling/createMessage", Some(sampler)) => sampler.create_message(&self.name, params).await,
+            (other, _) => Err(anyhow!("Method not supported by this client: {}", other)),
+        };
+        self.respond(id, result).await
+    }
+
+    /// Answer a request the server sent.
+    pub async fn respond(&mut self, id: serde_json::Value, result: Result<serde_json::Value>) -> Result<()> {
+        let msg = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": -32603, "message": format!("{:#}", e)}}),
+        };
+        self.send(msg).await
+    }
+
+    /// Send a request and wait for the response with the same id.
+    async fn request(&mut self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        self.request_serving(method, params, None).await
+    }
+
+    /// Like `request`, answering requests the server sends before it responds
+    /// (it may need them answered to produce its response).
+    async fn request_serving(
+        &mut self,
+        method: &str,
+        params: serde_json::Value,
+        sampler: Option<&Sampler<'_>>,
+    ) -> Result<serde_json::Value> {
         let id = self.next_id();
-        let req = json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "method": "tools/call",
-            "params": {"name": name, "arguments": args}
-        });
+        let (tx, mut rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let _entry = PendingEntry { pending: self.pending.clone(), id };
+        let req = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
         self.send(req).await?;
-        let resp = self.read().await?;
+        let mut listening = true;
+        // Time spent answering the server's own requests (e.g. a sampling approval) does not count
+        let mut deadline = self.timeout.map(|t| tokio::time::Instant::now() + t);
+        let resp = loop {
+            tokio::select! {
+                resp = &mut rx => break resp.map_err(|_| anyhow!("MCP server closed stdout"))?,
+                msg = self.incoming.recv(), if listening => match msg {
+                    Some(ServerMessage::Request { id, method, params }) => {
+                        let start = Instant::now();
+                        self.serve(id, &method, &params, sampler).await?;
+                        deadline = deadline.map(|d| d + start.elapsed());
+                    }
+                    Some(notification) => self.deferred.push(notification),
+                    None => listening = false,
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {
+                    let reason = format!("no response within {}s", self.timeout.unwrap_or_default().as_secs());
+                    let cancel = json!({"requestId": id, "reason": reason});
+                    self.send(json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": cancel})).await.ok();
+                    bail!("MCP server {} did not answer {} ({})", self.name, method, reason);
+                }
+            }
+        };
+        if resp.get("error").is_some() { return Err(anyhow!(format!("MCP error: {}", resp["error"]))); }
         Ok(resp["result"].clone())
     }
 
@@ -87,14 +339,89 @@ impl McpClient {
         self.stdin.flush().await?;
         Ok(())
     }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_responses_notifications_and_requests() {
+        assert_eq!(
+            classify(json!({"jsonrpc":"2.0","id":3,"result":{}})),
+            Incoming::Response(3, json!({"jsonrpc":"2.0","id":3,"result":{}}))
+        );
+        assert_eq!(
+            classify(json!({"jsonrpc":"2.0","method":"notifications/tools/list_changed"})),
+            Incoming::Server(ServerMessage::Notification {
+                method: "notifications/tools/list_changed".into(),
+                params: serde_json::Value::Null
+            })
+        );
+        assert!(matches!(
+            classify(json!({"jsonrpc":"2.0","id":"a","method":"ping"})),
+            Incoming::Server(ServerMessage::Request { .. })
+        ));
+        assert_eq!(classify(json!({"jsonrpc":"2.0"})), Incoming::Invalid);
+    }
+
+    #[tokio::test]
+    async fn drops_lines_over_the_size_limit() {
+        let mut input: &[u8] = b"{\"id\":1}\n0123456789abcdef\n{\"id\":2}";
+        assert_eq!(read_line(&mut input, Some(10)).await.unwrap(), Line::Text("{\"id\":1}\n".into()));
+        assert_eq!(read_line(&mut input, Some(10)).await.unwrap(), Line::TooLong(17));
+        assert_eq!(read_line(&mut input, Some(10)).await.unwrap(), Line::Text("{\"id\":2}".into()));
+        assert_eq!(read_line(&mut input, None).await.unwrap(), Line::Eof);
+    }
+
+    #[tokio::test]
+    async fn fails_only_the_single_waiting_request_on_an_oversized_line() {
+        let wait = |pending: &Pending, id: u64| {
+            let (tx, rx) = oneshot::channel();
+            pending.lock().unwrap().insert(id, tx);
+            rx
+        };
+        let (tx, _rx) = mpsc::unbounded_channel();
+
+        let pending = Pending::default();
+        let only = wait(&pending, 1);
+        let input = std::io::Cursor::new(b"0123456789abcdef\n".to_vec());
+        read_loop("one".into(), input, Some(10), pending, tx.clone()).await;
+        assert_eq!(only.await.unwrap()["error"]["code"], -32000);
+
+        // With two requests waiting, neither is failed; the other one's response still arrives
+        let pending = Pending::default();
+        let (first, second) = (wait(&pending, 1), wait(&pending, 2));
+        let mut output = vec![b'x'; 100];
+        output.extend_from_slice(b"\n{\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{}}\n");
+        let input = std::io::Cursor::new(output);
+        read_loop("two".into(), input, Some(64), pending.clone(), tx).await;
+        assert_eq!(second.await.unwrap()["result"], json!({}));
+        // Request 1 got no error; it was only dropped when the output closed
+        assert!(first.await.is_err());
+    }
 
-    async fn read(&mut self) -> Result<serde_json::Value> {
-        let mut reader = BufReader::new(&mut self.stdout);
-        let mut line = String::new();
-        let n = reader.read_line(&mut line).await?;
-        if n == 0 { return Err(anyhow!("MCP server closed stdout")); }
-        let v: serde_json::Value = serde_json::from_str(&line).context("Invalid JSON-RPC line")?;
-        if v.get("error").is_some() { return Err(anyhow!(format!("MCP error: {}", v["error"]))); }
-        Ok(v)
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn forgets_requests_that_could_not_be_sent() {
+        // A server that has closed its stdin but is still running
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "exec 0<&-; sleep 5"])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let (stdin, stdout) = (child.stdin.take().unwrap(), child.stdout.take().unwrap());
+        let mut client = McpClient::new("dead".into(), child, stdin, stdout, None);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(client.request("ping", json!({})).await.is_err());
+        assert!(client.pending.lock().unwrap().is_empty());
     }
 }
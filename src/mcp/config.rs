@@ -1,12 +1,22 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
+use std::{collections::HashMap, fs};
+
+use crate::mcp::policy::{PolicyConfig, Tag};
+use crate::secrets;
+use crate::mcp::sampling::SamplingConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct McpConfig {
     /// List of MCP servers to start/connect.
     pub servers: Vec<McpServerConfig>,
+    /// What to do with tool calls, per capability tag (see `tags`).
+    #[serde(default)]
+    pub policies: PolicyConfig,
+    /// Whether and how servers may ask the model for completions (`sampling/createMessage`).
+    #[serde(default)]
+    pub sampling: SamplingConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -24,20 +34,382 @@ pub struct McpServerConfig {
     /// Optional working directory.
     #[serde(default)]
     pub cwd: Option<String>,
+    /// Optional per-tool post-processing of results before they reach the model, keyed by tool name.
+    #[serde(default)]
+    pub transforms: HashMap<String, ToolTransform>,
+    /// Maximum size of a tool result sent to the model, in characters of JSON (default 20000, 0 = no limit).
+    /// Longer results keep their head and tail with an elision marker.
+    #[serde(default)]
+    pub max_result_chars: Option<usize>,
+    /// Per-tool overrides of `max_result_chars`, keyed by tool name.
+    #[serde(default)]
+    pub tool_max_result_chars: HashMap<String, usize>,
+    /// Capability tags per tool name (read, write, network, destructive), checked against `policies`.
+    #[serde(default)]
+    pub tags: HashMap<String, Vec<Tag>>,
+    /// Pass this process's environment to the server (default true). With false it gets only `env`
+    /// plus what is needed to start programs: PATH and a few OS variables (see `BASE_ENV`).
+    #[serde(default = "default_true")]
+    pub inherit_env: bool,
+    /// Fail a request the server has not answered within this many seconds (default: wait indefinitely).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Largest single message accepted from the server, in bytes; a longer one fails the request in flight.
+    #[serde(default)]
+    pub max_output_bytes: Option<usize>,
+    /// Command prefix that starts the server inside a sandbox or as another user, e.g.
+    /// `["docker", "run", "--rm", "-i", "mcp/fetch"]` or `["sudo", "-u", "mcp", "--"]`.
+    /// `command` and `args` are appended to it.
+    #[serde(default)]
+    pub run_as: Vec<String>,
 }
 
+fn default_true() -> bool {
+    true
+}
+
+/// Variables kept for servers with `inherit_env: false`, so programs can still be found and run.
+pub const BASE_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "LANG", "TMPDIR", "TEMP", "TMP", "SystemRoot", "SYSTEMROOT", "ComSpec", "PATHEXT",
+    "USERPROFILE", "APPDATA", "LOCALAPPDATA",
+];
+
+impl McpServerConfig {
+    /// Program and arguments to start, with `run_as` in front when set.
+    pub fn command_line(&self) -> (String, Vec<String>) {
+        match self.run_as.split_first() {
+            Some((program, prefix)) => {
+                let mut args = prefix.to_vec();
+                args.push(self.command.clone());
+                args.extend(self.args.iter().cloned());
+                (program.clone(), args)
+            }
+            None => (self.command.clone(), self.args.clone()),
+        }
+    }
+}
+
+/// Post-processing applied to a tool's result to strip noisy fields.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ToolTransform {
+    /// jq-style path selecting part of the result, e.g. `.items[]` or `.data.records[0]`.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Fields to keep (dotted paths allowed, e.g. `user.login`); arrays are filtered per element.
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+/// An environment variable for a server process. Set exactly one of `value`, `value_from_env`, or `value_from_keyring`.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct EnvVar {
     pub key: String,
-    pub value: String,
+    /// Literal value; `${NAME}` references are expanded.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Copy the value of this variable from the CLI's own environment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_from_env: Option<String>,
+    /// Read the value from the system keyring entry with this name (service `rust-openai-chat`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_from_keyring: Option<String>,
+}
+
+impl EnvVar {
+    fn resolve(&self, env: &dyn Fn(&str) -> Option<String>, keyring: &dyn Fn(&str) -> Result<String>) -> Result<String> {
+        match (&self.value, &self.value_from_env, &self.value_from_keyring) {
+            (Some(value), None, None) => expand(value, env),
+            (None, Some(name), None) => env(name).ok_or_else(|| anyhow!("environment variable {} is not set", name)),
+            (None, None, Some(name)) => keyring(name),
+            _ => bail!("set exactly one of value, value_from_env, or value_from_keyring"),
+        }
+    }
+}
+
+/// The first `${input:id}` reference in `text`. VS Code prompts for these; the CLI cannot.
+fn unresolved_input(text: &str) -> Option<&str> {
+    let start = text.find("${input:")?;
+    let end = text[start..].find('}').map_or(text.len(), |i| start + i + 1);
+    Some(&text[start..end])
+}
+
+fn warn_unresolved_input(server: &str, field: &str, text: &str) {
+    if let Some(input) = unresolved_input(text) {
+        eprintln!("⚠️ MCP server {}: {} contains {}, which is passed to the server unchanged", server, field, input);
+    }
+}
+
+/// VS Code's predefined variables. Configs imported from VS Code may use them; they are left as they are.
+const VSCODE_VARIABLES: &[&str] = &[
+    "workspaceFolder",
+    "workspaceFolderBasename",
+    "workspaceRoot",
+    "userHome",
+    "cwd",
+    "file",
+    "fileBasename",
+    "fileBasenameNoExtension",
+    "fileDirname",
+    "fileExtname",
+    "relativeFile",
+    "relativeFileDirname",
+    "lineNumber",
+    "selectedText",
+    "execPath",
+    "pathSeparator",
+    "/",
+];
+
+/// Replace `${NAME}` (or `${NAME:-default}`) with environment values; `$${` is a literal `${`.
+///
+/// VS Code's `${env:NAME}` reads the environment too (empty when unset, as in VS Code). Other VS Code
+/// references (`${input:id}`, `${workspaceFolder}`, ...) cannot be resolved here and are kept verbatim.
+pub fn expand(text: &str, env: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| anyhow!("unterminated ${{ in '{}'", text))? + start;
+        let reference = &rest[start + 2..end];
+        rest = &rest[end + 1..];
+        if let Some((scope, arg)) = reference.split_once(':').filter(|(_, arg)| !arg.starts_with('-')) {
+            match scope {
+                "env" => out.push_str(&env(arg).unwrap_or_default()),
+                _ => out.push_str(&format!("${{{}}}", reference)),
+            }
+            continue;
+        }
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if default.is_none() && VSCODE_VARIABLES.contains(&name) && env(name).is_none() {
+            out.push_str(&format!("${{{}}}", name));
+            continue;
+        }
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("invalid variable reference '${{{}}}'", reference);
+        }
+        match (env(name), default) {
+            (Some(value), _) => out.push_str(&value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => bail!("environment variable {} is not set (use ${{{}:-default}} for a fallback)", name, name),
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
 }
 
 impl McpConfig {
+    /// Load a YAML or JSON (`.json`) config. Besides the `servers:` list, the `mcpServers` map used by
+    /// Claude Desktop and the `servers` map used by VS Code are accepted.
     pub fn load_from_path(path: &str) -> Result<Self> {
         let s = fs::read_to_string(path)
             .with_context(|| format!("Failed to read MCP config from {}", path))?;
-        let cfg: McpConfig = serde_yaml::from_str(&s)
-            .with_context(|| format!("Invalid MCP config YAML in {}", path))?;
+        let mut cfg = if path.to_lowercase().ends_with(".json") {
+            Self::parse_json(&s).with_context(|| format!("Invalid MCP config JSON in {}", path))?
+        } else {
+            serde_yaml::from_str(&s).with_context(|| format!("Invalid MCP config YAML in {}", path))?
+        };
+        cfg.resolve(&|name| std::env::var(name).ok(), &|name| secrets::get(name))
+            .with_context(|| format!("Invalid MCP config {}", path))?;
+        for server in &cfg.servers {
+            for (tool, transform) in &server.transforms {
+                transform
+                    .validate()
+                    .with_context(|| format!("Invalid transform for {}.{} in {}", server.name, tool, path))?;
+            }
+        }
         Ok(cfg)
     }
+
+    fn parse_json(text: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        let map = match (value.get("mcpServers"), value.get("servers")) {
+            (Some(map @ serde_json::Value::Object(_)), _) | (None, Some(map @ serde_json::Value::Object(_))) => map,
+            // Already in the native shape; parse the text again for errors with line numbers
+            _ => return Ok(serde_json::from_str(text)?),
+        };
+        let mut servers = Vec::new();
+        for (name, entry) in map.as_object().into_iter().flatten() {
+            if entry.get("command").is_none() {
+                eprintln!("[MCP] Skipping server {}: only stdio servers (with a command) are supported", name);
+                continue;
+            }
+            let mut entry = entry.clone();
+            entry["name"] = serde_json::Value::String(name.clone());
+            // `env` is a map of KEY: value there, a list of {key, value} here
+            if let Some(env) = entry.get("env").and_then(|e| e.as_object()) {
+                let list: Vec<serde_json::Value> =
+                    env.iter().map(|(key, value)| serde_json::json!({"key": key, "value": value})).collect();
+                entry["env"] = serde_json::Value::Array(list);
+            }
+            servers.push(serde_json::from_value(entry).with_context(|| format!("server {}", name))?);
+        }
+        Ok(Self {
+            servers,
+            policies: value.get("policies").map(|p| serde_json::from_value(p.clone())).transpose()?.unwrap_or_default(),
+            sampling: value.get("sampling").map(|p| serde_json::from_value(p.clone())).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// JSON Schema of the config file, for editor validation.
+    pub fn schema() -> String {
+        serde_json::to_string_pretty(&schemars::schema_for!(McpConfig)).unwrap_or_default()
+    }
+
+    /// Expand `${NAME}` in `command`, `args`, `cwd`, and env values, and fill in env values taken from the
+    /// environment or the keyring, so that every `EnvVar::value` is set.
+    pub fn resolve(&mut self, env: &dyn Fn(&str) -> Option<String>, keyring: &dyn Fn(&str) -> Result<String>) -> Result<()> {
+        for server in &mut self.servers {
+            let name = server.name.clone();
+            server.command = expand(&server.command, env).with_context(|| format!("server {}: command", name))?;
+            warn_unresolved_input(&name, "command", &server.command);
+            for (i, arg) in server.args.iter_mut().enumerate() {
+                *arg = expand(arg, env).with_context(|| format!("server {}: args[{}]", name, i))?;
+                warn_unresolved_input(&name, &format!("args[{}]", i), arg);
+            }
+            for (i, arg) in server.run_as.iter_mut().enumerate() {
+                *arg = expand(arg, env).with_context(|| format!("server {}: run_as[{}]", name, i))?;
+                warn_unresolved_input(&name, &format!("run_as[{}]", i), arg);
+            }
+            if let Some(cwd) = &mut server.cwd {
+                *cwd = expand(cwd, env).with_context(|| format!("server {}: cwd", name))?;
+                warn_unresolved_input(&name, "cwd", cwd);
+            }
+            for var in &mut server.env {
+                let value = var.resolve(env, keyring).with_context(|| format!("server {}: env {}", name, var.key))?;
+                if let Some(input) = unresolved_input(&value) {
+                    bail!(
+                        "server {}: env {}: {} is a VS Code input prompt; set value_from_env or value_from_keyring instead",
+                        name,
+                        var.key,
+                        input
+                    );
+                }
+                var.value = Some(value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        (name == "HOME").then(|| "/home/me".to_string())
+    }
+
+    #[test]
+    fn expands_references() {
+        assert_eq!(expand("${HOME}/data", &lookup).unwrap(), "/home/me/data");
+        assert_eq!(expand("${MISSING:-fallback} $${HOME}", &lookup).unwrap(), "fallback ${HOME}");
+        assert!(expand("${MISSING}", &lookup).is_err());
+        assert!(expand("${HOME", &lookup).is_err());
+        assert_eq!(expand("${env:HOME} ${env:NOPE}|", &lookup).unwrap(), "/home/me |");
+        assert_eq!(expand("${input:token} ${workspaceFolder}", &lookup).unwrap(), "${input:token} ${workspaceFolder}");
+    }
+
+    #[test]
+    fn keeps_vscode_references_it_cannot_resolve() {
+        let json = r#"{
+            "inputs": [
+                {"type": "promptString", "id": "github-token", "description": "GitHub token", "password": true}
+            ],
+            "servers": {
+                "github": {
+                    "type": "stdio",
+                    "command": "docker",
+                    "args": ["run", "-i", "--rm", "-e", "GITHUB_PERSONAL_ACCESS_TOKEN", "ghcr.io/github/github-mcp-server"],
+                    "env": {"GITHUB_PERSONAL_ACCESS_TOKEN": "${input:github-token}"}
+                },
+                "files": {
+                    "command": "npx",
+                    "args": ["-y", "@modelcontextprotocol/server-filesystem", "${workspaceFolder}", "${userHome}${/}notes"],
+                    "env": {"HOME_DIR": "${env:HOME}", "EDITOR": "${env:EDITOR}"}
+                },
+                "remote": {"type": "http", "url": "https://api.githubcopilot.com/mcp/"}
+            }
+        }"#;
+        let mut cfg = McpConfig::parse_json(json).unwrap();
+        let err = cfg.resolve(&lookup, &|_| bail!("no keyring")).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "server github: env GITHUB_PERSONAL_ACCESS_TOKEN: ${input:github-token} is a VS Code input prompt; \
+             set value_from_env or value_from_keyring instead"
+        );
+
+        let mut cfg = McpConfig::parse_json(&json.replace("${input:github-token}", "${env:HOME}")).unwrap();
+        cfg.resolve(&lookup, &|_| bail!("no keyring")).unwrap();
+        assert_eq!(cfg.servers.len(), 2);
+        let files = cfg.servers.iter().find(|s| s.name == "files").unwrap();
+        assert_eq!(files.args[2..], ["${workspaceFolder}", "${userHome}${/}notes"]);
+        let env = |key: &str| files.env.iter().find(|v| v.key == key).and_then(|v| v.value.clone());
+        assert_eq!(env("HOME_DIR").as_deref(), Some("/home/me"));
+        assert_eq!(env("EDITOR").as_deref(), Some(""));
+        let github = cfg.servers.iter().find(|s| s.name == "github").unwrap();
+        assert_eq!(github.env[0].value.as_deref(), Some("/home/me"));
+        assert_eq!(unresolved_input("--token=${input:tok}x"), Some("${input:tok}"));
+    }
+
+    #[test]
+    fn reads_mcp_servers_maps() {
+        let json = r#"{
+            "mcpServers": {
+                "files": {"command": "npx", "args": ["-y", "files-server"], "env": {"ROOT": "/tmp"}},
+                "remote": {"url": "https://example.com/mcp"}
+            },
+            "policies": {"write": "deny"}
+        }"#;
+        let cfg = McpConfig::parse_json(json).unwrap();
+        assert_eq!(cfg.servers.len(), 1);
+        assert_eq!(cfg.servers[0].name, "files");
+        assert_eq!(cfg.servers[0].env[0].key, "ROOT");
+        assert_eq!(cfg.servers[0].env[0].value.as_deref(), Some("/tmp"));
+        assert_eq!(cfg.policies.write, crate::mcp::policy::Decision::Deny);
+
+        let native = McpConfig::parse_json(r#"{"servers": [{"name": "a", "command": "a"}]}"#).unwrap();
+        assert_eq!(native.servers[0].name, "a");
+        assert!(native.servers[0].inherit_env);
+        assert_eq!(native.servers[0].command_line(), ("a".to_string(), vec![]));
+        assert!(McpConfig::schema().contains("McpServerConfig"));
+    }
+
+    #[test]
+    fn resolves_env_values_at_load_time() {
+        let yaml = r#"
+servers:
+  - name: gh
+    command: ${HOME}/bin/gh-mcp
+    args: [--stdio]
+    run_as: [docker, run, --rm, -i, -v, "${HOME}:/data", gh-mcp]
+    env:
+      - key: HOME_DIR
+        value_from_env: HOME
+      - key: TOKEN
+        value_from_keyring: gh-token
+"#;
+        let mut cfg: McpConfig = serde_yaml::from_str(yaml).unwrap();
+        cfg.resolve(&lookup, &|name| Ok(format!("secret-for-{}", name))).unwrap();
+        let server = &cfg.servers[0];
+        assert_eq!(server.command, "/home/me/bin/gh-mcp");
+        let (program, args) = server.command_line();
+        assert_eq!(program, "docker");
+        assert_eq!(args, ["run", "--rm", "-i", "-v", "/home/me:/data", "gh-mcp", "/home/me/bin/gh-mcp", "--stdio"]);
+        assert_eq!(server.env[0].value.as_deref(), Some("/home/me"));
+        assert_eq!(server.env[1].value.as_deref(), Some("secret-for-gh-token"));
+
+        let bad = "servers:\n  - name: x\n    command: x\n    env:\n      - key: A\n        value_from_env: NOPE\n";
+        let mut cfg: McpConfig = serde_yaml::from_str(bad).unwrap();
+        let err = cfg.resolve(&lookup, &|_| bail!("no keyring")).unwrap_err();
+        assert_eq!(format!("{:#}", err), "server x: env A: environment variable NOPE is not set");
+    }
 }
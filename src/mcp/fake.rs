@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Deterministic tools offered by `mcp fake-server`.
+fn tools() -> Value {
+    json!([
+        {
+            "name": "echo",
+            "description": "Return the given text unchanged.",
+            "inputSchema": {"type": "object", "properties": {"text": {"type": "string"}}, "required": ["text"]}
+        },
+        {
+            "name": "add",
+            "description": "Add two numbers.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"a": {"type": "number"}, "b": {"type": "number"}},
+                "required": ["a", "b"]
+            }
+        },
+        {
+            "name": "fail",
+            "description": "Always fail, to check error handling.",
+            "inputSchema": {"type": "object", "properties": {}}
+        },
+        {
+            "name": "sample",
+            "description": "Ask the client's model (sampling/createMessage) and return its reply.",
+            "inputSchema": {"type": "object", "properties": {"prompt": {"type": "string"}}, "required": ["prompt"]}
+        },
+        {
+            "name": "big",
+            "description": "Return `chars` characters of text, to check result size limits.",
+            "inputSchema": {"type": "object", "properties": {"chars": {"type": "integer"}}}
+        },
+        {
+            "name": "sleep",
+            "description": "Wait `ms` milliseconds before answering, to check timeouts.",
+            "inputSchema": {"type": "object", "properties": {"ms": {"type": "integer"}}, "required": ["ms"]}
+        }
+    ])
+}
+
+fn text_result(text: impl Into<String>) -> Value {
+    json!({"content": [{"type": "text", "text": text.into()}]})
+}
+
+fn call_tool(name: &str, args: &Value) -> std::result::Result<Value, String> {
+    match name {
+        "echo" => Ok(text_result(args["text"].as_str().unwrap_or_default())),
+        "add" => {
+            let (Some(a), Some(b)) = (args["a"].as_f64(), args["b"].as_f64()) else {
+                return Err("add needs numeric 'a' and 'b'".to_string());
+            };
+            let mut result = text_result((a + b).to_string());
+            result["structuredContent"] = json!({"sum": a + b});
+            Ok(result)
+        }
+        "fail" => Ok(json!({"content": [{"type": "text", "text": "fake failure"}], "isError": true})),
+        "big" => {
+            let chars = args["chars"].as_u64().unwrap_or(50_000) as usize;
+            let line = "0123456789abcdefghijklmnopqrstuvwxyz\n";
+            Ok(text_result(line.chars().cycle().take(chars).collect::<String>()))
+        }
+        "sleep" => {
+            let ms = args["ms"].as_u64().unwrap_or_default();
+            std::thread::sleep(std::time::Duration::from_millis(ms));
+            Ok(text_result(format!("slept {} ms", ms)))
+        }
+        other => Err(format!("Unknown tool: {}", other)),
+    }
+}
+
+/// Response to one JSON-RPC message; notifications get none.
+pub fn handle(message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let method = message["method"].as_str().unwrap_or_default();
+    let result = match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "serverInfo": {"name": "fake-server", "version": env!("CARGO_PKG_VERSION")},
+            "capabilities": {"tools": {}}
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({"tools": tools()})),
+        "tools/call" => call_tool(message["params"]["name"].as_str().unwrap_or_default(), &message["params"]["arguments"])
+            .map_err(|e| (-32602, e)),
+        other => Err((-32601, format!("Method not found: {}", other))),
+    };
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}),
+    })
+}
+
+type Lines = tokio::io::Lines<BufReader<tokio::io::Stdin>>;
+
+async fn write(stdout: &mut tokio::io::Stdout, message: &Value) -> Result<()> {
+    stdout.write_all(format!("{}\n", message).as_bytes()).await?;
+    stdout.flush().await?;
+    Ok(())
+}
+
+async fn read(lines: &mut Lines) -> Result<Option<Value>> {
+    while let Some(line) = lines.next_line().await.context("Failed to read stdin")? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        return Ok(Some(serde_json::from_str(&line).unwrap_or_else(|e| json!({"parse_error": e.to_string()}))));
+    }
+    Ok(None)
+}
+
+/// The `sample` tool: send `sampling/createMessage` to the client and wait for its answer,
+/// serving anything else the client sends meanwhile.
+async fn sample(prompt: &str, lines: &mut Lines, stdout: &mut tokio::io::Stdout) -> Result<Value> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": "sample",
+        "method": "sampling/createMessage",
+        "params": {"messages": [{"role": "user", "content": {"type": "text", "text": prompt}}], "maxTokens": 100}
+    });
+    write(stdout, &request).await?;
+    while let Some(message) = read(lines).await? {
+        if message["id"] == "sample" && message.get("method").is_none() {
+            return Ok(match message["result"]["content"]["text"].as_str() {
+                Some(text) => text_result(text),
+                None => json!({"content": [{"type": "text", "text": message["error"].to_string()}], "isError": true}),
+            });
+        }
+        if let Some(response) = handle(&message) {
+            write(stdout, &response).await?;
+        }
+    }
+    anyhow::bail!("stdin closed while waiting for the sampling response")
+}
+
+/// Serve JSON-RPC over stdin/stdout, one message per line, until stdin closes.
+/// Notifications from the client are logged to stderr, so hosts can check what they sent.
+pub async fn run() -> Result<()> {
+    eprintln!("fake-server {} ready", env!("CARGO_PKG_VERSION"));
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(message) = read(&mut lines).await? {
+        let response = if let Some(e) = message.get("parse_error") {
+            Some(json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32700, "message": e}}))
+        } else if message["method"] == "tools/call" && message["params"]["name"] == "sample" {
+            let prompt = message["params"]["arguments"]["prompt"].as_str().unwrap_or_default();
+            let result = sample(prompt, &mut lines, &mut stdout).await?;
+            Some(json!({"jsonrpc": "2.0", "id": message["id"], "result": result}))
+        } else {
+            if message.get("id").is_none() {
+                eprintln!("notification {} {}", message["method"].as_str().unwrap_or_default(), message["params"]);
+            }
+            handle(&message)
+        };
+        if let Some(response) = response {
+            write(&mut stdout, &response).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: Value) -> Value {
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name, "arguments": arguments}});
+        handle(&request).unwrap()
+    }
+
+    #[test]
+    fn answers_requests_deterministically() {
+        assert_eq!(call("echo", json!({"text": "hi"}))["result"]["content"][0]["text"], "hi");
+        assert_eq!(call("add", json!({"a": 2, "b": 3}))["result"]["structuredContent"]["sum"], 5.0);
+        assert_eq!(call("fail", json!({}))["result"]["isError"], true);
+        assert_eq!(call("big", json!({"chars": 100}))["result"]["content"][0]["text"].as_str().unwrap().len(), 100);
+        assert_eq!(call("nope", json!({}))["error"]["code"], -32602);
+
+        let list = handle(&json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).unwrap();
+        assert_eq!(list["result"]["tools"].as_array().unwrap().len(), 6);
+        assert!(handle(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).is_none());
+    }
+}
@@ -1,27 +1,72 @@
-use crate::mcp::client::{McpClient, McpToolDescription};
-use crate::mcp::config::{EnvVar, McpConfig, McpServerConfig};
+use crate::mcp::client::{McpClient, McpServerInfo, McpToolDescription, ServerMessage};
+use crate::mcp::config::{EnvVar, McpConfig, McpServerConfig, ToolTransform, BASE_ENV};
+use crate::mcp::limits;
+use crate::mcp::logs::{self, ServerLog};
+use crate::chat::ChatClient;
+use crate::mcp::policy::{Decision, Policy};
+use crate::mcp::sampling::{Sampler, SamplingConfig};
+use crate::stats::ToolStatsMap;
 use anyhow::{Context, Result};
-use std::{collections::HashMap, process::Stdio};
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::process::Command;
 
 pub struct McpHost {
     clients: HashMap<String, McpClient>,
     pub tools: HashMap<String, (String /*server*/, McpToolDescription)>,
+    /// Result transforms keyed by (server, tool).
+    transforms: HashMap<(String, String), ToolTransform>,
+    /// Result size limits keyed by (server, tool), with per-server defaults under an empty tool name.
+    limits: HashMap<(String, String), usize>,
+    /// Captured stderr per server.
+    logs: HashMap<String, Arc<ServerLog>>,
+    /// Calls per tool, for /stats.
+    pub tool_stats: ToolStatsMap,
+    /// Tags and policies checked before each tool call.
+    pub policy: Policy,
+    /// How servers' sampling requests are handled.
+    pub sampling: SamplingConfig,
 }
 
 impl McpHost {
-    pub async fn from_config(cfg: McpConfig) -> Result<Self> {
+    /// Start every configured server. Their stderr goes to per-server logs; with `verbose` it is also echoed.
+    pub async fn from_config(cfg: McpConfig, verbose: bool) -> Result<Self> {
         let mut clients = HashMap::new();
+        let mut transforms = HashMap::new();
+        let mut server_logs = HashMap::new();
+        let mut limits = HashMap::new();
+        let mut policy = Policy { config: cfg.policies, ..Default::default() };
         for s in cfg.servers {
-            if let Ok(client) = spawn_server(&s).await {
-                clients.insert(s.name.clone(), client);
+            for (tool, tags) in &s.tags {
+                policy.tags.insert((s.name.clone(), tool.clone()), tags.clone());
+            }
+            if let Some(max) = s.max_result_chars {
+                limits.insert((s.name.clone(), String::new()), max);
+            }
+            for (tool, max) in &s.tool_max_result_chars {
+                limits.insert((s.name.clone(), tool.clone()), *max);
+            }
+            for (tool, t) in &s.transforms {
+                transforms.insert((s.name.clone(), tool.clone()), t.clone());
+            }
+            let log = Arc::new(ServerLog::new(Some(logs::default_log_path(&s.name))));
+            match spawn_server(&s, log.clone(), verbose).await {
+                Ok(client) => {
+                    clients.insert(s.name.clone(), client);
+                    server_logs.insert(s.name.clone(), log);
+                }
+                Err(e) => eprintln!("[MCP] {:#}", e),
             }
         }
 
         // Initialize clients and gather tools
         let mut tools = HashMap::new();
         for (name, client) in clients.iter_mut() {
-            if let Err(e) = client.initialize().await {
+            if let Err(e) = client.initialize(cfg.sampling.enabled()).await {
                 eprintln!("[MCP] initialize failed for {}: {}", name, e);
                 continue;
             }
@@ -35,25 +80,251 @@ impl McpHost {
             }
         }
 
-        Ok(Self { clients, tools })
+        Ok(Self { clients, tools, transforms, limits, logs: server_logs, tool_stats: ToolStatsMap::new(), policy, sampling: cfg.sampling })
+    }
+
+    /// Name and `initialize` info of every running server, sorted by name.
+    pub fn servers(&self) -> Vec<(String, Option<McpServerInfo>)> {
+        let mut list: Vec<_> = self
+            .clients
+            .iter()
+            .map(|(name, client)| (name.clone(), client.server_info.clone()))
+            .collect();
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+        list
+    }
+
+    /// Captured stderr of `server`, if it is running.
+    pub fn log(&self, server: &str) -> Option<&ServerLog> {
+        self.logs.get(server).map(|l| l.as_ref())
     }
 
-    pub async fn call(&mut self, tool: &str, args: serde_json::Value) -> Result<serde_json::Value> {
+    /// Whether handling a turn may prompt the user: a tool or sampling policy says "ask".
+    pub fn may_prompt(&self) -> bool {
+        self.sampling.approve == Decision::Ask
+            || self.tools.iter().any(|(tool, (server, _))| self.policy.evaluate(server, tool).0 == Decision::Ask)
+    }
+
+    /// Handle notifications and requests servers sent since the last check.
+    /// A `notifications/tools/list_changed` re-lists that server's tools.
+    pub async fn process_server_messages(&mut self, chat: &ChatClient, interactive: bool) {
+        let sampler = Sampler { chat, config: &self.sampling, interactive };
+        let mut changed = Vec::new();
+        for (name, client) in self.clients.iter_mut() {
+            for msg in client.take_messages() {
+                match msg {
+                    ServerMessage::Notification { method, .. } => {
+                        if method == "notifications/tools/list_changed" && !changed.contains(name) {
+                            changed.push(name.clone());
+                        }
+                    }
+                    ServerMessage::Request { id, method, params } => {
+                        if let Err(e) = client.serve(id, &method, &params, Some(&sampler)).await {
+                            eprintln!("[MCP] Failed to answer {} from {}: {}", method, name, e);
+                        }
+                    }
+                }
+            }
+        }
+        for server in changed {
+            if let Err(e) = self.refresh_tools(&server).await {
+                eprintln!("[MCP] tools/list failed for {}: {}", server, e);
+            }
+        }
+    }
+
+    /// Replace the tools registered for `server` with a fresh `tools/list`.
+    async fn refresh_tools(&mut self, server: &str) -> Result<()> {
+        let client = self.clients.get_mut(server).context("Server not found")?;
+        let list = client.list_tools().await?;
+        let before: Vec<String> =
+            self.tools.iter().filter(|(_, (s, _))| s == server).map(|(name, _)| name.clone()).collect();
+        self.tools.retain(|_, (s, _)| s != server);
+        let mut added = Vec::new();
+        for t in list {
+            if !before.contains(&t.name) {
+                added.push(t.name.clone());
+            }
+            self.tools.insert(t.name.clone(), (server.to_string(), t));
+        }
+        let removed: Vec<&String> = before.iter().filter(|n| !self.tools.contains_key(*n)).collect();
+        eprintln!(
+            "[MCP] {} updated its tools ({} total; added: {}; removed: {})",
+            server,
+            self.tools.values().filter(|(s, _)| s == server).count(),
+            if added.is_empty() { "none".to_string() } else { added.join(", ") },
+            if removed.is_empty() { "none".to_string() } else { removed.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ") }
+        );
+        Ok(())
+    }
+
+    /// Call `tool`; sampling requests its server makes meanwhile are answered with `chat`.
+    pub async fn call(
+        &mut self,
+        tool: &str,
+        args: serde_json::Value,
+        chat: &ChatClient,
+        interactive: bool,
+    ) -> Result<serde_json::Value> {
+        let start = Instant::now();
+        let result = self.call_inner(tool, args, chat, interactive).await;
+        let stats = self.tool_stats.entry(tool.to_string()).or_default();
+        stats.calls += 1;
+        stats.total_time += start.elapsed();
+        // Tool-level failures come back as results with isError set
+        if result.as_ref().map_or(true, |r| r["isError"] == true) {
+            stats.errors += 1;
+        }
+        result
+    }
+
+    async fn call_inner(
+        &mut self,
+        tool: &str,
+        args: serde_json::Value,
+        chat: &ChatClient,
+        interactive: bool,
+    ) -> Result<serde_json::Value> {
         let (server, _desc) = self.tools.get(tool).context("Unknown tool")?.clone();
         let client = self.clients.get_mut(&server).context("Server not found")?;
-        client.call_tool(tool, args).await
+        let sampler = Sampler { chat, config: &self.sampling, interactive };
+        let result = client.call_tool(tool, args, Some(&sampler)).await?;
+        let key = (server, tool.to_string());
+        let result = match self.transforms.get(&key) {
+            Some(t) => t.apply(&result),
+            None => result,
+        };
+        let max = self
+            .limits
+            .get(&key)
+            .or_else(|| self.limits.get(&(key.0.clone(), String::new())))
+            .copied()
+            .unwrap_or(limits::DEFAULT_MAX_RESULT_CHARS);
+        Ok(limits::limit_result(result, max))
     }
 }
 
-async fn spawn_server(cfg: &McpServerConfig) -> Result<McpClient> {
-    let mut cmd = Command::new(&cfg.command);
-    cmd.args(&cfg.args);
+async fn spawn_server(cfg: &McpServerConfig, log: Arc<ServerLog>, verbose: bool) -> Result<McpClient> {
+    let (program, args) = cfg.command_line();
+    let mut cmd = Command::new(&program);
+    cmd.args(&args);
+    if !cfg.inherit_env {
+        cmd.env_clear();
+        for (key, value) in std::env::vars_os().filter(|(k, _)| BASE_ENV.iter().any(|b| k == *b)) {
+            cmd.env(key, value);
+        }
+    }
     if let Some(cwd) = &cfg.cwd { cmd.current_dir(cwd); }
-    for EnvVar { key, value } in &cfg.env { cmd.env(key, value); }
-    cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit());
+    for EnvVar { key, value, .. } in &cfg.env { cmd.env(key, value.as_deref().unwrap_or_default()); }
+    cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
 
     let mut child = cmd.spawn().with_context(|| format!("Failed to start MCP server {}", cfg.name))?;
     let stdin = child.stdin.take().context("Failed to open stdin")?;
     let stdout = child.stdout.take().context("Failed to open stdout")?;
-    Ok(McpClient::new(cfg.name.clone(), child, stdin, stdout))
+    let stderr = child.stderr.take().context("Failed to open stderr")?;
+    logs::spawn_stderr_reader(cfg.name.clone(), stderr, log, verbose);
+    let mut client = McpClient::new(cfg.name.clone(), child, stdin, stdout, cfg.max_output_bytes);
+    client.timeout = cfg.timeout_secs.map(Duration::from_secs);
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::logs::LOG_CAPACITY;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// The `rust-openai-chat` binary next to this test executable; `cargo test` builds it for the integration tests.
+    fn cli_binary() -> String {
+        let exe = std::env::current_exe().unwrap();
+        let dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+        dir.join(format!("rust-openai-chat{}", std::env::consts::EXE_SUFFIX)).display().to_string()
+    }
+
+    /// Answer one Chat Completions request with `reply` and return the request body.
+    async fn answer_once(listener: TcpListener, reply: &str) -> serde_json::Value {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        let body_start = loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                break i + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+        let length: usize = head
+            .lines()
+            .find_map(|l| l.strip_prefix("content-length:"))
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or_default();
+        while request.len() < body_start + length {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        let message = json!({"role": "assistant", "content": reply});
+        let body = json!({"model": "gpt-4o", "choices": [{"index": 0, "message": message, "finish_reason": "stop"}]}).to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        serde_json::from_slice(&request[body_start..]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn calls_the_fake_server_through_the_host() {
+        let yaml = format!(
+            "servers:\n  - name: fake\n    command: {:?}\n    args: [mcp, fake-server]\n    timeout_secs: 1\n    \
+             tool_max_result_chars: {{big: 1000}}\nsampling:\n  approve: allow\n",
+            cli_binary()
+        );
+        let cfg: McpConfig = serde_yaml::from_str(&yaml).unwrap();
+        let mut host = McpHost::from_config(cfg, false).await.unwrap();
+        assert_eq!(host.tools.len(), 6, "is the binary built? {}", cli_binary());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let chat = ChatClient::new(endpoint, "key".into(), "gpt-4o".into(), "2024-10-21".into());
+
+        let echo = host.call("echo", json!({"text": "hi"}), &chat, false).await.unwrap();
+        assert_eq!(echo["content"][0]["text"], "hi");
+        let sum = host.call("add", json!({"a": 2, "b": 3}), &chat, false).await.unwrap();
+        assert_eq!(sum["structuredContent"]["sum"], 5.0);
+
+        let failed = host.call("fail", json!({}), &chat, false).await.unwrap();
+        assert_eq!(failed["isError"], true);
+        assert_eq!(host.tool_stats["fail"].errors, 1);
+
+        let big = host.call("big", json!({"chars": 5000}), &chat, false).await.unwrap();
+        assert_eq!(big["truncated"]["max_chars"], 1000);
+        assert!(big["truncated"]["original_chars"].as_u64().unwrap() > 5000);
+        assert!(big["content"][0]["text"].as_str().unwrap().contains("characters omitted"));
+
+        // The server's sampling request goes to the model, and the model's reply comes back as the tool result
+        let call = host.call("sample", json!({"prompt": "ping"}), &chat, false);
+        let (request, sampled) = tokio::join!(answer_once(listener, "pong"), call);
+        assert_eq!(request["messages"][0], json!({"role": "user", "content": "ping"}));
+        assert_eq!(sampled.unwrap()["content"][0]["text"], "pong");
+
+        let err = host.call("sleep", json!({"ms": 1500}), &chat, false).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("did not answer tools/call"), "{:#}", err);
+        // The fake server logs the cancellation once it has finished sleeping
+        let log = host.log("fake").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let cancelled = loop {
+            let lines = log.tail(LOG_CAPACITY);
+            if let Some(line) = lines.iter().find(|l| l.starts_with("notification notifications/cancelled")) {
+                break line.clone();
+            }
+            assert!(Instant::now() < deadline, "no cancellation in the server log: {:?}", lines);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        assert!(cancelled.contains("no response within 1s"), "{}", cancelled);
+    }
 }
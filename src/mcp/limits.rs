@@ -0,0 +1,107 @@
+use serde_json::Value;
+
+/// Size limit for one tool result (as sent to the model) when the config sets none.
+pub const DEFAULT_MAX_RESULT_CHARS: usize = 20_000;
+
+/// Keep the head and tail of `text` within `max` characters, with a marker saying how much was cut.
+/// Cuts snap to nearby line breaks so the kept parts stay readable.
+pub fn truncate_middle(text: &str, max: usize) -> String {
+    let total = text.chars().count();
+    if total <= max {
+        return text.to_string();
+    }
+    let head_len = max * 2 / 3;
+    let tail_len = max - head_len;
+    let mut head: String = text.chars().take(head_len).collect();
+    let mut tail: String = text.chars().skip(total - tail_len).collect();
+
+    // Prefer whole lines when a line break is close to the cut
+    if let Some(i) = head.rfind('\n').filter(|&i| i >= head.len() * 4 / 5) {
+        head.truncate(i + 1);
+    }
+    if let Some(i) = tail.find('\n').filter(|&i| i <= tail.len() / 5) {
+        tail.drain(..=i);
+    }
+    let omitted = total - head.chars().count() - tail.chars().count();
+    format!(
+        "{}\n[… {} of {} characters omitted …]\n{}",
+        head.trim_end_matches('\n'),
+        omitted,
+        total,
+        tail
+    )
+}
+
+/// Shrink a tool result so its JSON fits in `max` characters (0 means no limit).
+///
+/// Text content items are shortened in proportion to their size so the result stays valid JSON;
+/// if that is not enough the whole result is cut as text. Truncated results carry
+/// `truncated: {original_chars, max_chars}` so the model knows content is missing.
+pub fn limit_result(result: Value, max: usize) -> Value {
+    let original = serde_json::to_string(&result).unwrap_or_default();
+    let size = original.chars().count();
+    if max == 0 || size <= max {
+        return result;
+    }
+    let note = serde_json::json!({"original_chars": size, "max_chars": max});
+
+    let mut out = result;
+    if let Some(items) = out.get_mut("content").and_then(|c| c.as_array_mut()) {
+        let text_chars: usize = items.iter().filter_map(|i| i["text"].as_str()).map(|t| t.chars().count()).sum();
+        // Leave room for the rest of the JSON, escaping, and the markers
+        let overhead = size - text_chars + 200;
+        if text_chars > 0 && overhead < max {
+            let budget = max - overhead;
+            for item in items.iter_mut() {
+                let Some(text) = item["text"].as_str() else { continue };
+                let len = text.chars().count();
+                let share = budget * len / text_chars;
+                if len > share {
+                    item["text"] = Value::String(truncate_middle(text, share));
+                }
+            }
+            if let Some(obj) = out.as_object_mut() {
+                obj.insert("truncated".to_string(), note.clone());
+            }
+            if serde_json::to_string(&out).map(|s| s.chars().count() <= max).unwrap_or(false) {
+                return out;
+            }
+        }
+    }
+    serde_json::json!({
+        "content": [{"type": "text", "text": truncate_middle(&original, max.saturating_sub(200))}],
+        "truncated": note,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_head_and_tail_with_a_marker() {
+        let text: String = (0..100).map(|i| format!("line {}\n", i)).collect();
+        let cut = truncate_middle(&text, 120);
+        assert!(cut.starts_with("line 0\n"));
+        assert!(cut.ends_with("line 99\n"));
+        assert!(cut.contains("characters omitted …]"));
+        assert!(cut.contains(&format!("of {} characters", text.len())));
+        assert_eq!(truncate_middle("short", 10), "short");
+    }
+
+    #[test]
+    fn shrinks_text_content_and_records_the_original_size() {
+        let big = "x".repeat(50_000);
+        let result = json!({"content": [{"type": "text", "text": big}], "isError": false});
+        let limited = limit_result(result, 1_000);
+        assert!(serde_json::to_string(&limited).unwrap().len() <= 1_000);
+        assert_eq!(limited["isError"], false);
+        assert_eq!(limited["truncated"]["max_chars"], 1_000);
+        assert!(limited["truncated"]["original_chars"].as_u64().unwrap() > 50_000);
+
+        let small = json!({"content": [{"type": "text", "text": "ok"}]});
+        assert_eq!(limit_result(small.clone(), 1_000), small);
+        assert_eq!(limit_result(json!({"data": big}), 0)["data"].as_str().unwrap().len(), 50_000);
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::ChildStderr,
+};
+
+/// Recent stderr lines kept in memory per server.
+pub const LOG_CAPACITY: usize = 500;
+
+/// Stderr of one MCP server: the last `LOG_CAPACITY` lines plus an append-only log file.
+#[derive(Debug)]
+pub struct ServerLog {
+    lines: Mutex<VecDeque<String>>,
+    /// Where every line is also appended, when the logs directory is writable.
+    pub path: Option<PathBuf>,
+}
+
+impl ServerLog {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { lines: Mutex::new(VecDeque::with_capacity(LOG_CAPACITY)), path }
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == LOG_CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// The last `n` lines, oldest first.
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().skip(lines.len().saturating_sub(n)).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.lock().unwrap().len()
+    }
+}
+
+/// Default log location for a server: `<data dir>/rust-openai-chat/logs/mcp-<server>.log`.
+pub fn default_log_path(server: &str) -> PathBuf {
+    let safe: String = server
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("rust-openai-chat")
+        .join("logs")
+        .join(format!("mcp-{}.log", safe))
+}
+
+/// Copy a server's stderr into `log` until it closes; with `verbose`, also echo each line tagged with the server name.
+pub fn spawn_stderr_reader(name: String, stderr: ChildStderr, log: Arc<ServerLog>, verbose: bool) {
+    tokio::spawn(async move {
+        let mut file = log.path.as_ref().and_then(|p| {
+            std::fs::create_dir_all(p.parent()?).ok()?;
+            std::fs::OpenOptions::new().create(true).append(true).open(p).ok()
+        });
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if verbose {
+                eprintln!("[{}] {}", name, line);
+            }
+            if let Some(f) = file.as_mut() {
+                let _ = writeln!(f, "{} {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), line);
+            }
+            log.push(line);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_most_recent_lines() {
+        let log = ServerLog::new(None);
+        for i in 0..LOG_CAPACITY + 5 {
+            log.push(format!("line {}", i));
+        }
+        assert_eq!(log.len(), LOG_CAPACITY);
+        assert_eq!(log.tail(2), vec![format!("line {}", LOG_CAPACITY + 3), format!("line {}", LOG_CAPACITY + 4)]);
+        assert_eq!(log.tail(10_000).first().unwrap(), "line 5");
+    }
+}
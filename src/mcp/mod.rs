@@ -1,3 +1,9 @@
 pub mod config;
 pub mod client;
+pub mod fake;
 pub mod host;
+pub mod limits;
+pub mod logs;
+pub mod policy;
+pub mod sampling;
+pub mod transform;
@@ -0,0 +1,151 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+/// What a tool can do, as declared in the MCP config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Tag {
+    Read,
+    Write,
+    Network,
+    Destructive,
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Tag::Read => "read",
+            Tag::Write => "write",
+            Tag::Network => "network",
+            Tag::Destructive => "destructive",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Whether a tool call may run. Ordered from most to least permissive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    Ask,
+    Deny,
+}
+
+/// Decisions per tag, set under `policies:` in the MCP config.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PolicyConfig {
+    #[serde(default = "allow")]
+    pub read: Decision,
+    #[serde(default = "ask")]
+    pub write: Decision,
+    #[serde(default = "allow")]
+    pub network: Decision,
+    #[serde(default = "ask")]
+    pub destructive: Decision,
+    /// Tools with no tags.
+    #[serde(default = "allow")]
+    pub untagged: Decision,
+}
+
+fn allow() -> Decision {
+    Decision::Allow
+}
+
+fn ask() -> Decision {
+    Decision::Ask
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            read: Decision::Allow,
+            write: Decision::Ask,
+            network: Decision::Allow,
+            destructive: Decision::Ask,
+            untagged: Decision::Allow,
+        }
+    }
+}
+
+/// Evaluates tool calls against tags and policies before they run.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub config: PolicyConfig,
+    /// `--read-only`: deny anything that may write, and tools whose effects are unknown.
+    pub read_only: bool,
+    /// Tags keyed by (server, tool).
+    pub tags: HashMap<(String, String), Vec<Tag>>,
+}
+
+impl Policy {
+    fn for_tag(&self, tag: Tag) -> Decision {
+        match tag {
+            Tag::Read => self.config.read,
+            Tag::Write => self.config.write,
+            Tag::Network => self.config.network,
+            Tag::Destructive => self.config.destructive,
+        }
+    }
+
+    /// Decision for `server`'s `tool` plus a short reason; with several tags the strictest wins.
+    pub fn evaluate(&self, server: &str, tool: &str) -> (Decision, String) {
+        let tags = self.tags.get(&(server.to_string(), tool.to_string())).map(|t| t.as_slice()).unwrap_or_default();
+        if self.read_only {
+            if let Some(tag) = tags.iter().find(|t| matches!(t, Tag::Write | Tag::Destructive)) {
+                return (Decision::Deny, format!("tagged '{}' and --read-only is set", tag));
+            }
+            if tags.is_empty() {
+                return (Decision::Deny, "untagged and --read-only is set".to_string());
+            }
+        }
+        if tags.is_empty() {
+            return (self.config.untagged, "untagged".to_string());
+        }
+        let decision = tags.iter().map(|t| self.for_tag(*t)).max().unwrap_or(Decision::Allow);
+        let names: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+        (decision, format!("tagged {}", names.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(read_only: bool) -> Policy {
+        let mut tags = HashMap::new();
+        tags.insert(("files".to_string(), "read_file".to_string()), vec![Tag::Read]);
+        tags.insert(("files".to_string(), "write_file".to_string()), vec![Tag::Write]);
+        tags.insert(("web".to_string(), "fetch_and_delete".to_string()), vec![Tag::Network, Tag::Destructive]);
+        Policy { config: PolicyConfig::default(), read_only, tags }
+    }
+
+    #[test]
+    fn strictest_tag_wins() {
+        let p = policy(false);
+        assert_eq!(p.evaluate("files", "read_file").0, Decision::Allow);
+        assert_eq!(p.evaluate("files", "write_file").0, Decision::Ask);
+        assert_eq!(p.evaluate("web", "fetch_and_delete").0, Decision::Ask);
+        assert_eq!(p.evaluate("files", "unknown").0, Decision::Allow);
+        // Tags belong to one server; another server's tool of the same name is untagged
+        assert_eq!(p.evaluate("web", "write_file").0, Decision::Allow);
+    }
+
+    #[test]
+    fn read_only_denies_writes_and_untagged_tools() {
+        let p = policy(true);
+        assert_eq!(p.evaluate("files", "read_file").0, Decision::Allow);
+        assert_eq!(p.evaluate("files", "write_file").0, Decision::Deny);
+        assert_eq!(p.evaluate("web", "fetch_and_delete").0, Decision::Deny);
+        assert_eq!(p.evaluate("files", "unknown").0, Decision::Deny);
+    }
+
+    #[test]
+    fn parses_policies_from_yaml() {
+        let cfg: PolicyConfig = serde_yaml::from_str("write: deny\nuntagged: ask").unwrap();
+        assert_eq!(cfg.write, Decision::Deny);
+        assert_eq!(cfg.untagged, Decision::Ask);
+        assert_eq!(cfg.destructive, Decision::Ask);
+    }
+}
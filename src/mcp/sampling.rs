@@ -0,0 +1,167 @@
+use anyhow::{anyhow, bail, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::chat::ChatClient;
+use crate::mcp::policy::Decision;
+use crate::ui;
+
+/// How `sampling/createMessage` requests from servers are handled (`sampling:` in the MCP config).
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SamplingConfig {
+    /// allow, ask (default), or deny. With deny the capability is not offered to servers.
+    #[serde(default = "ask")]
+    pub approve: Decision,
+    /// Upper bound on the `maxTokens` a server may ask for.
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    /// Deployment that answers sampling requests; defaults to the chat deployment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployment: Option<String>,
+}
+
+fn ask() -> Decision {
+    Decision::Ask
+}
+
+fn default_max_tokens() -> u32 {
+    1000
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self { approve: Decision::Ask, max_tokens: default_max_tokens(), deployment: None }
+    }
+}
+
+impl SamplingConfig {
+    pub fn enabled(&self) -> bool {
+        self.approve != Decision::Deny
+    }
+}
+
+/// Answers sampling requests with the chat client, for as long as a turn runs.
+pub struct Sampler<'a> {
+    pub chat: &'a ChatClient,
+    pub config: &'a SamplingConfig,
+    /// Whether the user can be asked to approve; without a terminal "ask" counts as deny.
+    pub interactive: bool,
+}
+
+impl Sampler<'_> {
+    /// Handle `sampling/createMessage` from `server` and build its result.
+    pub async fn create_message(&self, server: &str, params: &Value) -> Result<Value> {
+        let messages = to_chat_messages(params)?;
+        let max_tokens = params["maxTokens"]
+            .as_u64()
+            .map_or(self.config.max_tokens, |n| (n as u32).min(self.config.max_tokens));
+        let approved = match self.config.approve {
+            Decision::Allow => true,
+            Decision::Deny => false,
+            Decision::Ask if self.interactive => {
+                let _pause = ui::pause_spinner();
+                let last = messages.last().and_then(|m| m["content"].as_str()).unwrap_or("");
+                let preview: String = last.lines().next().unwrap_or("").chars().take(80).collect();
+                println!("\r🔁 MCP server '{}' asks the model ({} messages): {}", server, messages.len(), preview);
+                Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("Allow this sampling request (up to {} tokens)?", max_tokens))
+                    .default(false)
+                    .interact()
+                    .context("Failed to read confirmation")?
+            }
+            Decision::Ask => false,
+        };
+        if !approved {
+            bail!("Sampling request declined by the user or client policy");
+        }
+        let temperature = params["temperature"].as_f64().map(|t| t as f32);
+        let deployment = self.config.deployment.as_deref();
+        let response = self.chat.sample(deployment, &messages, max_tokens, temperature).await?;
+        if let Some(tokens) = response["usage"]["total_tokens"].as_u64() {
+            eprintln!("[MCP] {} sampling used {} tokens", server, tokens);
+        }
+        to_result(&response)
+    }
+}
+
+/// Chat messages for a sampling request: `systemPrompt` plus each message's text or image content.
+pub fn to_chat_messages(params: &Value) -> Result<Vec<Value>> {
+    let mut messages = Vec::new();
+    if let Some(system) = params["systemPrompt"].as_str().filter(|s| !s.is_empty()) {
+        messages.push(json!({"role": "system", "content": system}));
+    }
+    let list = params["messages"].as_array().ok_or_else(|| anyhow!("sampling request has no messages"))?;
+    for m in list {
+        let role = match m["role"].as_str() {
+            Some(role @ ("user" | "assistant")) => role,
+            other => bail!("unsupported sampling message role: {:?}", other),
+        };
+        let parts: Vec<&Value> = match &m["content"] {
+            Value::Array(items) => items.iter().collect(),
+            item => vec![item],
+        };
+        let content = match parts.as_slice() {
+            [part] if part["type"] == "text" => json!(part["text"].as_str().unwrap_or_default()),
+            _ => Value::Array(parts.iter().map(|p| content_part(p)).collect::<Result<_>>()?),
+        };
+        messages.push(json!({"role": role, "content": content}));
+    }
+    Ok(messages)
+}
+
+fn content_part(part: &Value) -> Result<Value> {
+    match part["type"].as_str() {
+        Some("text") => Ok(json!({"type": "text", "text": part["text"]})),
+        Some("image") => {
+            let mime = part["mimeType"].as_str().unwrap_or("image/png");
+            let data = part["data"].as_str().unwrap_or_default();
+            Ok(json!({"type": "image_url", "image_url": {"url": format!("data:{};base64,{}", mime, data)}}))
+        }
+        other => bail!("unsupported sampling content type: {:?}", other),
+    }
+}
+
+/// `CreateMessageResult` for a chat completion response.
+pub fn to_result(response: &Value) -> Result<Value> {
+    let choice = response["choices"].get(0).context("No response choices available")?;
+    let stop_reason = match choice["finish_reason"].as_str() {
+        Some("length") => "maxTokens",
+        Some("stop") | None => "endTurn",
+        Some(other) => other,
+    };
+    Ok(json!({
+        "role": "assistant",
+        "content": {"type": "text", "text": choice["message"]["content"].as_str().unwrap_or_default()},
+        "model": response["model"].as_str().unwrap_or_default(),
+        "stopReason": stop_reason,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_requests_and_results() {
+        let params = json!({
+            "systemPrompt": "Be brief.",
+            "messages": [
+                {"role": "user", "content": {"type": "text", "text": "Summarize this"}},
+                {"role": "user", "content": {"type": "image", "data": "AAAA", "mimeType": "image/jpeg"}}
+            ],
+            "maxTokens": 50
+        });
+        let messages = to_chat_messages(&params).unwrap();
+        assert_eq!(messages[0], json!({"role": "system", "content": "Be brief."}));
+        assert_eq!(messages[1]["content"], "Summarize this");
+        assert_eq!(messages[2]["content"][0]["image_url"]["url"], "data:image/jpeg;base64,AAAA");
+        assert!(to_chat_messages(&json!({"messages": [{"role": "tool", "content": {}}]})).is_err());
+
+        let response = json!({"model": "gpt-4o", "choices": [{"message": {"content": "Short."}, "finish_reason": "length"}]});
+        let result = to_result(&response).unwrap();
+        assert_eq!(result["content"]["text"], "Short.");
+        assert_eq!(result["stopReason"], "maxTokens");
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+use crate::mcp::config::ToolTransform;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Iterate,
+}
+
+/// Parse a jq-style path such as `.`, `.items`, `.items[0].name`, or `.items[].title`.
+fn parse_path(expr: &str) -> Result<Vec<Segment>> {
+    let expr = expr.trim();
+    if !expr.starts_with('.') {
+        return Err(anyhow!("path must start with '.': {}", expr));
+    }
+    let mut segments = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut key = String::new();
+                while let Some(&n) = chars.peek() {
+                    if n == '.' || n == '[' {
+                        break;
+                    }
+                    key.push(n);
+                    chars.next();
+                }
+                if !key.is_empty() {
+                    segments.push(Segment::Key(key));
+                }
+            }
+            '[' => {
+                let mut inner = String::new();
+                for n in chars.by_ref() {
+                    if n == ']' {
+                        break;
+                    }
+                    inner.push(n);
+                }
+                if inner.is_empty() {
+                    segments.push(Segment::Iterate);
+                } else {
+                    let i = inner
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow!("invalid index [{}] in path {}", inner, expr))?;
+                    segments.push(Segment::Index(i));
+                }
+            }
+            other => return Err(anyhow!("unexpected '{}' in path {}", other, expr)),
+        }
+    }
+    Ok(segments)
+}
+
+fn eval(v: &Value, segments: &[Segment]) -> Value {
+    let Some((first, rest)) = segments.split_first() else {
+        return v.clone();
+    };
+    match first {
+        Segment::Key(k) => v.get(k).map(|x| eval(x, rest)).unwrap_or(Value::Null),
+        Segment::Index(i) => v.get(*i).map(|x| eval(x, rest)).unwrap_or(Value::Null),
+        Segment::Iterate => match v {
+            Value::Array(items) => Value::Array(items.iter().map(|x| eval(x, rest)).collect()),
+            Value::Object(map) => Value::Array(map.values().map(|x| eval(x, rest)).collect()),
+            _ => Value::Null,
+        },
+    }
+}
+
+/// Keep only the listed (optionally dotted) fields; arrays are filtered element-wise.
+fn keep_fields(v: &Value, fields: &[String]) -> Value {
+    match v {
+        Value::Array(items) => Value::Array(items.iter().map(|x| keep_fields(x, fields)).collect()),
+        Value::Object(_) => {
+            let mut out = Value::Object(Map::new());
+            for field in fields {
+                let parts: Vec<&str> = field.split('.').collect();
+                if let Some(found) = lookup(v, &parts) {
+                    insert(&mut out, &parts, found.clone());
+                }
+            }
+            out
+        }
+        other => other.clone(),
+    }
+}
+
+fn lookup<'a>(v: &'a Value, parts: &[&str]) -> Option<&'a Value> {
+    parts.iter().try_fold(v, |cur, p| cur.get(*p))
+}
+
+fn insert(out: &mut Value, parts: &[&str], value: Value) {
+    let mut cur = out;
+    for (i, p) in parts.iter().enumerate() {
+        let map = match cur {
+            Value::Object(m) => m,
+            _ => return,
+        };
+        if i == parts.len() - 1 {
+            map.insert(p.to_string(), value);
+            return;
+        }
+        cur = map.entry(p.to_string()).or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+impl ToolTransform {
+    /// Check the expression at config load time.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(path) = &self.path {
+            parse_path(path)?;
+        }
+        Ok(())
+    }
+
+    fn apply_value(&self, v: &Value) -> Value {
+        let selected = match self.path.as_deref().map(parse_path) {
+            Some(Ok(segments)) => eval(v, &segments),
+            _ => v.clone(),
+        };
+        if self.fields.is_empty() { selected } else { keep_fields(&selected, &self.fields) }
+    }
+
+    /// Apply to a `tools/call` result. JSON carried in text content items (the usual MCP shape)
+    /// and `structuredContent` are transformed in place; any other result is transformed whole.
+    pub fn apply(&self, result: &Value) -> Value {
+        let mut out = result.clone();
+        let mut touched = false;
+        if let Some(items) = out.get_mut("content").and_then(|c| c.as_array_mut()) {
+            for item in items.iter_mut() {
+                let Some(text) = item.get("text").and_then(|t| t.as_str()) else { continue };
+                let Ok(parsed) = serde_json::from_str::<Value>(text) else { continue };
+                let transformed = self.apply_value(&parsed);
+                item["text"] = Value::String(serde_json::to_string(&transformed).unwrap_or_default());
+                touched = true;
+            }
+        }
+        if let Some(structured) = out.get("structuredContent").cloned() {
+            out["structuredContent"] = self.apply_value(&structured);
+            touched = true;
+        }
+        if touched { out } else { self.apply_value(result) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transform(path: Option<&str>, fields: &[&str]) -> ToolTransform {
+        ToolTransform {
+            path: path.map(|p| p.to_string()),
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn selects_path_and_fields() {
+        let v = json!({"total": 2, "items": [
+            {"title": "a", "id": 1, "user": {"login": "x", "avatar": "..."}},
+            {"title": "b", "id": 2, "user": {"login": "y", "avatar": "..."}}
+        ]});
+        let t = transform(Some(".items[]"), &["title", "user.login"]);
+        assert_eq!(
+            t.apply(&v),
+            json!([{"title": "a", "user": {"login": "x"}}, {"title": "b", "user": {"login": "y"}}])
+        );
+        assert_eq!(transform(Some(".items[1].id"), &[]).apply(&v), json!(2));
+    }
+
+    #[test]
+    fn transforms_json_inside_text_content() {
+        let result = json!({"content": [{"type": "text", "text": "{\"a\":1,\"noise\":[1,2,3]}"}]});
+        let out = transform(None, &["a"]).apply(&result);
+        assert_eq!(out["content"][0]["text"], "{\"a\":1}");
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        assert!(transform(Some("items"), &[]).validate().is_err());
+        assert!(transform(Some(".items[x]"), &[]).validate().is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::path::{Path, PathBuf};
+
+use crate::chat::ChatClient;
+use crate::errors;
+
+/// Generate one image with an image deployment (DALL-E 3 / gpt-image-1) and return the raw bytes.
+pub async fn generate_image(client: &ChatClient, deployment: &str, prompt: &str, size: &str) -> Result<Vec<u8>> {
+    let url = client.deployment_url(deployment, "images/generations");
+    let response = client
+        .post_json(&url, &serde_json::json!({ "prompt": prompt, "size": size, "n": 1 }), false)
+        .await
+        .context("Failed to send image generation request to Azure OpenAI")?;
+
+    let response = errors::check(response).await?;
+
+    let v: serde_json::Value = client.read_json(response).await.context("Failed to parse image response")?;
+    let item = v["data"].get(0).context("No image returned")?;
+    if let Some(b64) = item["b64_json"].as_str() {
+        return base64::engine::general_purpose::STANDARD
+            .decode(b64)
+            .context("Invalid base64 image data");
+    }
+    // DALL-E 3 returns a short-lived URL by default
+    let image_url = item["url"].as_str().context("Image response has neither b64_json nor url")?;
+    let bytes = reqwest::get(image_url)
+        .await
+        .context("Failed to download generated image")?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(bytes.to_vec())
+}
+
+/// Transcribe an audio file with a Whisper deployment (multipart upload); returns the text.
+pub async fn transcribe(client: &ChatClient, deployment: &str, file: &Path, language: Option<&str>) -> Result<String> {
+    let bytes = tokio::fs::read(file)
+        .await
+        .with_context(|| format!("Failed to read audio file {}", file.display()))?;
+    let file_name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "audio".to_string());
+    let mut fields = serde_json::json!({
+        "file": format!("{} ({} bytes)", file_name, bytes.len()),
+        "response_format": "json",
+    });
+    let mut form = reqwest::multipart::Form::new()
+        .part("file", reqwest::multipart::Part::bytes(bytes).file_name(file_name))
+        .text("response_format", "json");
+    if let Some(lang) = language {
+        form = form.text("language", lang.to_string());
+        fields["language"] = serde_json::json!(lang);
+    }
+
+    let url = client.deployment_url(deployment, "audio/transcriptions");
+    let response = client
+        .post_multipart(&url, form, &fields)
+        .await
+        .context("Failed to send transcription request to Azure OpenAI")?;
+
+    let response = errors::check(response).await?;
+
+    let v: serde_json::Value = client.read_json(response).await.context("Failed to parse transcription response")?;
+    Ok(v["text"].as_str().context("Transcription response has no text")?.to_string())
+}
+
+/// Synthesize speech with a TTS deployment; returns the encoded audio bytes.
+pub async fn speak(client: &ChatClient, deployment: &str, text: &str, voice: &str, format: &str) -> Result<Vec<u8>> {
+    let url = client.deployment_url(deployment, "audio/speech");
+    let response = client
+        .post_json(
+            &url,
+            &serde_json::json!({
+                "model": deployment,
+                "input": text,
+                "voice": voice,
+                "response_format": format
+            }),
+            false,
+        )
+        .await
+        .context("Failed to send speech request to Azure OpenAI")?;
+
+    let response = errors::check(response).await?;
+
+    Ok(response.bytes().await.context("Failed to read speech audio")?.to_vec())
+}
+
+/// TTS `response_format` for an output path, from its extension (defaults to mp3).
+pub fn audio_format_for(out: &Path) -> &'static str {
+    match out.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("opus") => "opus",
+        Some("aac") => "aac",
+        Some("flac") => "flac",
+        Some("wav") => "wav",
+        Some("pcm") => "pcm",
+        _ => "mp3",
+    }
+}
+
+/// Output path for variation `i` (1-based) of `n`: `fox.png` stays as-is for a single image,
+/// otherwise `fox-1.png`, `fox-2.png`, ...
+pub fn numbered_path(out: &Path, i: usize, n: usize) -> PathBuf {
+    if n <= 1 {
+        return out.to_path_buf();
+    }
+    let stem = out.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name = match out.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, i, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, i),
+    };
+    out.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_variations() {
+        let out = Path::new("out/fox.png");
+        assert_eq!(numbered_path(out, 1, 1), PathBuf::from("out/fox.png"));
+        assert_eq!(numbered_path(out, 2, 3), PathBuf::from("out/fox-2.png"));
+    }
+
+    #[test]
+    fn picks_audio_format_from_extension() {
+        assert_eq!(audio_format_for(Path::new("out.WAV")), "wav");
+        assert_eq!(audio_format_for(Path::new("out.mp3")), "mp3");
+        assert_eq!(audio_format_for(Path::new("out")), "mp3");
+    }
+}
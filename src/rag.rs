@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::chat::ChatClient;
+
+/// Target chunk size in characters, and overlap carried into the next chunk.
+const CHUNK_CHARS: usize = 1500;
+const CHUNK_OVERLAP: usize = 200;
+/// Inputs per embeddings request.
+const EMBED_BATCH: usize = 16;
+/// Files larger than this are skipped when indexing.
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "md", "markdown", "txt", "rst", "adoc", "org", "html", "htm", "csv", "json", "yaml", "yml", "toml",
+    "ini", "rs", "py", "js", "ts", "tsx", "jsx", "go", "java", "kt", "cs", "c", "h", "cpp", "hpp",
+    "rb", "php", "sh", "ps1", "sql", "swift", "scala",
+];
+
+/// On-disk vector store produced by `index`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VectorStore {
+    /// Embeddings deployment used; queries must be embedded with the same one.
+    pub embedding_model: String,
+    /// Directory that was indexed.
+    pub root: String,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Path relative to the indexed root.
+    pub source: String,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+impl VectorStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read index {}", path.display()))?;
+        serde_json::from_str(&s).with_context(|| format!("Invalid index file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create index directory {}", parent.display()))?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write index {}", path.display()))
+    }
+
+    /// Top-k chunks by cosine similarity to the query embedding.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(&Chunk, f32)> {
+        let mut scored: Vec<_> = self
+            .chunks
+            .iter()
+            .map(|c| (c, cosine_similarity(&c.embedding, query)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        scored
+    }
+}
+
+/// Default location for indexes: `<data dir>/rust-openai-chat/indexes`.
+pub fn default_index_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("rust-openai-chat")
+        .join("indexes")
+}
+
+/// Resolve an index argument: an existing file path, or a name in the default index directory.
+pub fn resolve_index_path(index: &str) -> PathBuf {
+    let p = PathBuf::from(index);
+    if p.is_file() {
+        p
+    } else {
+        default_index_dir().join(format!("{}.json", index))
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut na, mut nb) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    if na == 0.0 || nb == 0.0 { 0.0 } else { dot / (na.sqrt() * nb.sqrt()) }
+}
+
+/// Split text into chunks of roughly `CHUNK_CHARS`, preferring paragraph then line breaks.
+pub fn chunk_text(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + CHUNK_CHARS).min(chars.len());
+        if end < chars.len() {
+            let window: String = chars[start..end].iter().collect();
+            let cut = window
+                .rfind("\n\n")
+                .or_else(|| window.rfind('\n'))
+                .map(|byte_pos| window[..byte_pos].chars().count())
+                .filter(|n| *n > CHUNK_CHARS / 2);
+            if let Some(n) = cut {
+                end = start + n;
+            }
+        }
+        let chunk: String = chars[start..end].iter().collect();
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim().to_string());
+        }
+        if end == chars.len() {
+            break;
+        }
+        start = end.saturating_sub(CHUNK_OVERLAP).max(start + 1);
+    }
+    chunks
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name.starts_with('.') || name == "target" || name == "node_modules" {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, out)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| TEXT_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Chunk and embed every text file under `root`.
+pub async fn build_index(client: &ChatClient, root: &Path, embedding_model: &str) -> Result<VectorStore> {
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+    files.sort();
+
+    let mut pending: Vec<(String, String)> = Vec::new();
+    for file in &files {
+        if fs::metadata(file).map(|m| m.len() > MAX_FILE_BYTES).unwrap_or(true) {
+            eprintln!("[index] skipping {} (too large)", file.display());
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(file) else {
+            eprintln!("[index] skipping {} (not UTF-8)", file.display());
+            continue;
+        };
+        let source = file.strip_prefix(root).unwrap_or(file).to_string_lossy().replace('\\', "/");
+        for chunk in chunk_text(&text) {
+            pending.push((source.clone(), chunk));
+        }
+    }
+
+    let mut chunks = Vec::with_capacity(pending.len());
+    for (i, batch) in pending.chunks(EMBED_BATCH).enumerate() {
+        eprint!("\r[index] embedding {}/{} chunks", (i * EMBED_BATCH + batch.len()), pending.len());
+        let inputs: Vec<String> = batch.iter().map(|(_, t)| t.clone()).collect();
+        let embeddings = client.embed(embedding_model, &inputs).await?;
+        for ((source, text), embedding) in batch.iter().cloned().zip(embeddings) {
+            chunks.push(Chunk { source, text, embedding });
+        }
+    }
+    eprintln!();
+
+    Ok(VectorStore {
+        embedding_model: embedding_model.to_string(),
+        root: root.display().to_string(),
+        chunks,
+    })
+}
+
+/// Prepend retrieved chunks to the user's question.
+pub fn augment_prompt(question: &str, hits: &[(&Chunk, f32)]) -> String {
+    if hits.is_empty() {
+        return question.to_string();
+    }
+    let mut out = String::from(
+        "Use the following excerpts from local documents to answer. Cite sources by path when relevant.\n\n",
+    );
+    for (i, (chunk, _score)) in hits.iter().enumerate() {
+        out.push_str(&format!("[{}] {}\n{}\n\n", i + 1, chunk.source, chunk.text));
+    }
+    out.push_str("Question: ");
+    out.push_str(question);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_long_text_with_overlap() {
+        let para = "word ".repeat(100);
+        let text = format!("{p}\n\n{p}\n\n{p}\n\n{p}\n\n{p}", p = para.trim());
+        let chunks = chunk_text(&text);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.chars().count() <= CHUNK_CHARS));
+        assert_eq!(chunk_text("short"), vec!["short".to_string()]);
+    }
+
+    #[test]
+    fn ranks_by_cosine_similarity() {
+        let store = VectorStore {
+            embedding_model: "e".into(),
+            root: ".".into(),
+            chunks: vec![
+                Chunk { source: "a".into(), text: "a".into(), embedding: vec![1.0, 0.0] },
+                Chunk { source: "b".into(), text: "b".into(), embedding: vec![0.0, 1.0] },
+            ],
+        };
+        let hits = store.search(&[0.1, 0.9], 1);
+        assert_eq!(hits[0].0.source, "b");
+    }
+}
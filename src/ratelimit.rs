@@ -0,0 +1,136 @@
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Client-side requests/minute and tokens/minute limits over a sliding one-minute window.
+///
+/// Requests that would go over a limit wait until enough earlier requests leave the window,
+/// so the deployment's quota is respected before the API has to answer 429.
+pub struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u32>,
+    /// Send time and estimated tokens of recent requests, oldest first.
+    sent: Mutex<VecDeque<(Instant, u32)>>,
+}
+
+impl RateLimiter {
+    /// `None` when neither limit is set.
+    pub fn new(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> Option<Self> {
+        if requests_per_minute.is_none() && tokens_per_minute.is_none() {
+            return None;
+        }
+        Some(Self { requests_per_minute, tokens_per_minute, sent: Mutex::new(VecDeque::new()) })
+    }
+
+    /// How long a request of `tokens` must wait at `now` before it fits, if at all.
+    fn wait_needed(&self, sent: &VecDeque<(Instant, u32)>, now: Instant, tokens: u32) -> Option<Duration> {
+        let mut until: Option<Instant> = None;
+        if let Some(rpm) = self.requests_per_minute.filter(|r| *r > 0) {
+            if sent.len() >= rpm as usize {
+                until = Some(sent[sent.len() - rpm as usize].0 + WINDOW);
+            }
+        }
+        if let Some(tpm) = self.tokens_per_minute.filter(|t| *t > 0) {
+            let mut used: u32 = sent.iter().map(|(_, t)| *t).sum();
+            // A request larger than the whole budget goes once the window is empty
+            let budget = tpm.max(tokens);
+            for (at, t) in sent {
+                if used + tokens <= budget {
+                    break;
+                }
+                used -= t;
+                until = until.max(Some(*at + WINDOW));
+            }
+        }
+        until.map(|u| u.saturating_duration_since(now)).filter(|d| !d.is_zero())
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(rpm) = self.requests_per_minute {
+            parts.push(format!("{} requests/min", rpm));
+        }
+        if let Some(tpm) = self.tokens_per_minute {
+            parts.push(format!("{} tokens/min", tpm));
+        }
+        parts.join(", ")
+    }
+
+    /// Wait until a request of about `tokens` fits, showing a countdown on stderr meanwhile, then record it.
+    pub async fn acquire(&self, tokens: u32) {
+        let mut waited = false;
+        loop {
+            let wait = {
+                let mut sent = self.sent.lock().unwrap();
+                let now = Instant::now();
+                while sent.front().is_some_and(|(at, _)| now.duration_since(*at) >= WINDOW) {
+                    sent.pop_front();
+                }
+                match self.wait_needed(&sent, now, tokens) {
+                    None => {
+                        sent.push_back((now, tokens));
+                        break;
+                    }
+                    Some(wait) => wait,
+                }
+            };
+            waited = true;
+            eprint!("\r⏳ Rate limit ({}): waiting {}s...   ", self.describe(), wait.as_secs_f32().ceil());
+            io::stderr().flush().ok();
+            tokio::time::sleep(wait.min(Duration::from_secs(1))).await;
+        }
+        if waited {
+            eprint!("\r{}\r", " ".repeat(60));
+        }
+    }
+}
+
+/// Tokens a request counts against the quota: the prompt at the model's `chars_per_token`
+/// (see `usage::chars_per_token`), plus the completion budget.
+pub fn estimate_tokens(body: &impl Serialize, chars_per_token: f32) -> u32 {
+    let value = serde_json::to_value(body).unwrap_or_default();
+    let prompt = (value.to_string().chars().count() as f32 / chars_per_token.max(0.1)) as usize;
+    let completion = ["max_tokens", "max_completion_tokens", "max_output_tokens"]
+        .iter()
+        .find_map(|k| value[k].as_u64())
+        .unwrap_or(0);
+    (prompt as u64 + completion).min(u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_the_oldest_request_to_leave_the_window() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let limiter = RateLimiter::new(Some(2), None).unwrap();
+        let sent = VecDeque::from([(at(0), 10), (at(10), 10)]);
+        assert_eq!(limiter.wait_needed(&sent, at(20), 10), Some(Duration::from_secs(40)));
+        assert_eq!(limiter.wait_needed(&VecDeque::from([(at(10), 10)]), at(20), 10), None);
+
+        let limiter = RateLimiter::new(None, Some(1000)).unwrap();
+        let sent = VecDeque::from([(at(0), 600), (at(30), 300)]);
+        assert_eq!(limiter.wait_needed(&sent, at(40), 100), None);
+        assert_eq!(limiter.wait_needed(&sent, at(40), 200), Some(Duration::from_secs(20)));
+        assert_eq!(limiter.wait_needed(&sent, at(40), 5000), Some(Duration::from_secs(50)));
+        assert_eq!(limiter.wait_needed(&VecDeque::new(), at(40), 5000), None);
+    }
+
+    #[test]
+    fn estimates_prompt_and_completion_tokens() {
+        let body = serde_json::json!({"messages": [{"role": "user", "content": "x".repeat(400)}], "max_tokens": 1000});
+        let tokens = estimate_tokens(&body, 4.0);
+        assert!((1100..1130).contains(&tokens), "{}", tokens);
+        // Finer-grained tokenizers (e.g. Llama) count the same prompt as more tokens
+        assert!(estimate_tokens(&body, 3.2) > tokens + 20);
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::chat::ChatRequest;
+use crate::trace::Tracer;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A persistent connection to the Azure OpenAI Realtime endpoint for text-only chat (`--realtime`).
+///
+/// The server keeps the conversation, so each turn sends only the messages it has not seen yet.
+/// When the local conversation no longer extends what was sent (after `clear`, `/switch`, ...),
+/// the connection is replaced by a fresh one and the whole conversation is sent again.
+pub struct Realtime {
+    url: String,
+    api_key: String,
+    socket: Option<Socket>,
+    /// Messages the server's conversation holds, as they were sent.
+    synced: Vec<Value>,
+    /// System prompt last sent with `session.update`.
+    instructions: Option<String>,
+}
+
+/// What has to be sent to bring the server's conversation up to date.
+#[derive(Debug, PartialEq, Eq)]
+enum Sync {
+    /// Send messages from this index on.
+    From(usize),
+    /// Start over on a new connection.
+    Reset,
+}
+
+fn plan(synced: &[Value], outgoing: &[Value]) -> Sync {
+    if outgoing.len() < synced.len() {
+        return Sync::Reset;
+    }
+    let last_user = synced.iter().rposition(|m| m["role"] == "user");
+    let same = synced.iter().zip(outgoing).enumerate().all(|(i, (sent, now))| {
+        // System prompts go with session.update, and the latest question may have carried --rag context
+        sent["role"] == now["role"] && (sent["role"] == "system" || Some(i) == last_user || sent["content"] == now["content"])
+    });
+    if same {
+        Sync::From(synced.len())
+    } else {
+        Sync::Reset
+    }
+}
+
+/// `conversation.item.create` event for a chat message.
+fn item_event(message: &Value) -> Value {
+    let role = message["role"].as_str().unwrap_or("user");
+    let kind = if role == "assistant" { "text" } else { "input_text" };
+    let text = message["content"].as_str().unwrap_or_default();
+    json!({
+        "type": "conversation.item.create",
+        "item": {"type": "message", "role": role, "content": [{"type": kind, "text": text}]},
+    })
+}
+
+/// A server event as the equivalent Chat Completions stream chunk. `Ok(None)` for events without text;
+/// an error for `error` events and failed responses.
+pub fn to_chat_chunk(event: &Value) -> Result<Option<Value>> {
+    Ok(match event["type"].as_str() {
+        Some("response.text.delta" | "response.output_text.delta") => {
+            Some(json!({"choices": [{"delta": {"content": event["delta"]}}]}))
+        }
+        Some("response.done") => {
+            let response = &event["response"];
+            if response["status"] == "failed" {
+                let error = &response["status_details"]["error"];
+                bail!("Realtime response failed: {}", error["message"].as_str().unwrap_or("unknown error"));
+            }
+            let u = &response["usage"];
+            let finish_reason = match response["status_details"]["reason"].as_str() {
+                Some("max_output_tokens") => "length",
+                _ => "stop",
+            };
+            Some(json!({
+                "choices": [{"delta": {}, "finish_reason": finish_reason}],
+                "usage": {
+                    "prompt_tokens": u["input_tokens"].as_u64().unwrap_or(0),
+                    "completion_tokens": u["output_tokens"].as_u64().unwrap_or(0),
+                    "total_tokens": u["total_tokens"].as_u64().unwrap_or(0),
+                },
+            }))
+        }
+        Some("error") => bail!("Realtime error: {}", event["error"]["message"].as_str().unwrap_or("unknown error")),
+        _ => None,
+    })
+}
+
+impl Realtime {
+    /// `wss://<resource>/openai/realtime?api-version=...&deployment=...` for an `https://` endpoint.
+    pub fn new(endpoint: &str, api_key: &str, deployment: &str, api_version: &str) -> Self {
+        let base = endpoint.trim_end_matches('/');
+        let base = match base.split_once("://") {
+            Some(("http", rest)) => format!("ws://{}", rest),
+            Some((_, rest)) => format!("wss://{}", rest),
+            None => format!("wss://{}", base),
+        };
+        Self {
+            url: format!("{}/openai/realtime?api-version={}&deployment={}", base, api_version, deployment),
+            api_key: api_key.to_string(),
+            socket: None,
+            synced: Vec::new(),
+            instructions: None,
+        }
+    }
+
+    async fn connect(&mut self, tracer: Option<&Tracer>) -> Result<()> {
+        let mut request = self.url.as_str().into_client_request().context("Invalid realtime URL")?;
+        request
+            .headers_mut()
+            .insert("api-key", HeaderValue::from_str(&self.api_key).context("Invalid API key")?);
+        if let Some(tracer) = tracer {
+            tracer.note(&format!("→ WebSocket {}", self.url));
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .context("Failed to connect to the Azure OpenAI Realtime endpoint")?;
+        self.socket = Some(socket);
+        self.synced.clear();
+        self.instructions = None;
+        Ok(())
+    }
+
+    async fn send(&mut self, event: Value, tracer: Option<&Tracer>) -> Result<()> {
+        let text = event.to_string();
+        if let Some(tracer) = tracer {
+            tracer.frame("→", &text);
+        }
+        let socket = self.socket.as_mut().context("Realtime connection is closed")?;
+        socket.send(Message::Text(text)).await.context("Failed to send to the realtime connection")
+    }
+
+    /// Run one turn for `outgoing` (the whole conversation), passing each text delta and the final
+    /// usage chunk to `on_chunk` as Chat Completions stream payloads.
+    pub async fn turn(
+        &mut self,
+        outgoing: &[Value],
+        request: &ChatRequest,
+        tracer: Option<&Tracer>,
+        on_chunk: impl FnMut(&str),
+    ) -> Result<()> {
+        let result = self.run_turn(outgoing, request, tracer, on_chunk).await;
+        if result.is_err() {
+            // The server may be mid-response; start clean next time
+            self.socket = None;
+        }
+        result
+    }
+
+    async fn run_turn(
+        &mut self,
+        outgoing: &[Value],
+        request: &ChatRequest,
+        tracer: Option<&Tracer>,
+        mut on_chunk: impl FnMut(&str),
+    ) -> Result<()> {
+        let start = match (self.socket.is_some(), plan(&self.synced, outgoing)) {
+            (true, Sync::From(start)) => start,
+            _ => {
+                self.connect(tracer).await?;
+                0
+            }
+        };
+        let instructions = outgoing.iter().find(|m| m["role"] == "system").and_then(|m| m["content"].as_str());
+        if instructions.map(str::to_string) != self.instructions {
+            let mut session = json!({"modalities": ["text"], "instructions": instructions.unwrap_or_default()});
+            if let Some(t) = request.temperature {
+                session["temperature"] = json!(t);
+            }
+            if let Some(max) = request.max_tokens.or(request.max_completion_tokens) {
+                session["max_response_output_tokens"] = json!(max);
+            }
+            self.send(json!({"type": "session.update", "session": session}), tracer).await?;
+            self.instructions = instructions.map(str::to_string);
+        }
+        for message in outgoing[start..].iter().filter(|m| m["role"] != "system") {
+            self.send(item_event(message), tracer).await?;
+        }
+        self.send(json!({"type": "response.create", "response": {"modalities": ["text"]}}), tracer).await?;
+
+        let mut reply = String::new();
+        loop {
+            let socket = self.socket.as_mut().context("Realtime connection is closed")?;
+            let message = match socket.next().await {
+                Some(message) => message.context("Realtime connection failed")?,
+                None => bail!("The realtime connection was closed by the server"),
+            };
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(frame) => {
+                    bail!("The realtime connection was closed: {}", frame.map(|f| f.reason.to_string()).unwrap_or_default())
+                }
+                _ => continue,
+            };
+            if let Some(tracer) = tracer {
+                tracer.frame("←", &text);
+            }
+            let Ok(event) = serde_json::from_str::<Value>(&text) else {
+                continue;
+            };
+            if let Some(chunk) = to_chat_chunk(&event)? {
+                if let Some(delta) = chunk["choices"][0]["delta"]["content"].as_str() {
+                    reply.push_str(delta);
+                }
+                on_chunk(&chunk.to_string());
+            }
+            if event["type"] == "response.done" {
+                break;
+            }
+        }
+        self.synced = outgoing.to_vec();
+        self.synced.push(json!({"role": "assistant", "content": reply}));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(role: &str, content: &str) -> Value {
+        json!({"role": role, "content": content})
+    }
+
+    #[test]
+    fn sends_only_what_the_server_lacks() {
+        let synced = vec![msg("system", "sys"), msg("user", "q1 with context"), msg("assistant", "a1")];
+        let next = vec![msg("system", "sys at 09:31"), msg("user", "q1"), msg("assistant", "a1"), msg("user", "q2")];
+        assert_eq!(plan(&synced, &next), Sync::From(3));
+        assert_eq!(plan(&synced, &[msg("system", "sys"), msg("user", "new")]), Sync::Reset);
+        let edited = vec![msg("system", "sys"), msg("user", "q1"), msg("assistant", "other"), msg("user", "q2")];
+        assert_eq!(plan(&synced, &edited), Sync::Reset);
+        assert_eq!(plan(&[], &next), Sync::From(0));
+    }
+
+    #[test]
+    fn converts_server_events() {
+        let delta = to_chat_chunk(&json!({"type": "response.text.delta", "delta": "Hi"})).unwrap().unwrap();
+        assert_eq!(delta["choices"][0]["delta"]["content"], "Hi");
+        let done = json!({"type": "response.done", "response": {
+            "status": "incomplete", "status_details": {"reason": "max_output_tokens"},
+            "usage": {"input_tokens": 3, "output_tokens": 2, "total_tokens": 5}
+        }});
+        let chunk = to_chat_chunk(&done).unwrap().unwrap();
+        assert_eq!(chunk["usage"]["total_tokens"], 5);
+        assert_eq!(chunk["choices"][0]["finish_reason"], "length");
+        assert!(to_chat_chunk(&json!({"type": "error", "error": {"message": "bad"}})).is_err());
+        assert!(Realtime::new("https://x.openai.azure.com/", "k", "rt", "v").url.starts_with("wss://x.openai.azure.com/openai/realtime?"));
+    }
+}
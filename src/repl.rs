@@ -0,0 +1,735 @@
+use anyhow::{bail, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::chat::{ChatClient, ChatReply};
+use crate::choice::{self, ChoicePolicy};
+use crate::clipboard::{self, Clipboard};
+use crate::clock::{self, Clock};
+use crate::mcp::host::McpHost;
+use crate::mcp::policy::{Decision, Policy};
+use crate::net;
+use crate::rag::{self, VectorStore};
+use crate::session::{self, Session, SessionEnv};
+use crate::settings::Settings;
+use crate::stats;
+use crate::tee::Tee;
+use crate::ui::{self, BannerInfo};
+use crate::usage::{self, Usage, UsageTracker};
+
+pub const SYSTEM_PROMPT: &str = "You are a helpful assistant.";
+/// Sent (but not kept in the history) by `/continue`; the answer is appended to the previous reply.
+const CONTINUE_PROMPT: &str =
+    "Continue exactly where your last reply stopped, without repeating anything or adding an introduction.";
+
+/// `--verbose` line for one round of tool calls: which tools, and how large their arguments are.
+fn round_diagnostics(round: usize, max: usize, tool_calls: &[serde_json::Value]) -> String {
+    let calls: Vec<String> = tool_calls
+        .iter()
+        .map(|tc| {
+            let f = &tc["function"];
+            let size = f["arguments"].as_str().map_or(0, str::len);
+            format!("{} ({} B args)", f["name"].as_str().unwrap_or("?"), size)
+        })
+        .collect();
+    format!("· tool round {}/{}: {}", round, max, calls.join(", "))
+}
+
+/// Options fixed for the lifetime of a chat.
+pub struct ReplOptions {
+    pub stream: bool,
+    pub show_reasoning: bool,
+    /// Save the conversation as a session after each turn.
+    pub save: bool,
+    pub sessions_dir: PathBuf,
+    /// How to pick a reply when several choices come back.
+    pub choice_policy: ChoicePolicy,
+    /// Whether a user is at the terminal to answer prompts (false in script mode).
+    pub interactive: bool,
+    /// Add the current date/time to the system prompt of every request.
+    pub clock: Option<Clock>,
+    /// Print each turn as one JSON record instead of the chat transcript (`script --json`).
+    pub json: bool,
+    /// Hide the tool-call spinner and per-call summaries (`--quiet`).
+    pub quiet: bool,
+    /// Stop a turn when the model still asks for tools after this many rounds of tool calls.
+    pub max_tool_rounds: usize,
+}
+
+/// Retrieval settings for `--rag` mode.
+pub struct RagOptions {
+    pub store: VectorStore,
+    pub embedding_model: String,
+    pub top_k: usize,
+}
+
+/// What the caller should do after a line has been handled.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Conversation state plus everything needed to run a turn.
+pub struct Repl {
+    pub client: ChatClient,
+    pub mcp_host: Option<McpHost>,
+    pub rag: Option<RagOptions>,
+    pub settings: Settings,
+    pub options: ReplOptions,
+    pub conversation: Vec<serde_json::Value>,
+    pub session_env: SessionEnv,
+    pub session: Session,
+    pub usage: UsageTracker,
+    /// Turns completed since the session started (or was cleared), for /stats.
+    pub turns: usize,
+    /// File receiving a copy of replies (`--output`, `/tee`).
+    pub tee: Option<Tee>,
+    clipboard: Clipboard,
+}
+
+fn initial_conversation() -> Vec<serde_json::Value> {
+    vec![serde_json::json!({"role":"system","content": SYSTEM_PROMPT})]
+}
+
+/// Check a tool call against the policy, asking the user when it says so.
+/// Without a terminal to ask at, "ask" counts as a denial.
+fn approve_tool(policy: &Policy, server: &str, name: &str, args: &serde_json::Value, interactive: bool) -> Result<bool> {
+    let (decision, reason) = policy.evaluate(server, name);
+    let allowed = match decision {
+        Decision::Allow => true,
+        Decision::Deny => false,
+        Decision::Ask if interactive => Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Allow tool '{}' ({}) with arguments {}?", name, reason, args))
+            .default(false)
+            .interact()
+            .context("Failed to read confirmation")?,
+        Decision::Ask => false,
+    };
+    if !allowed {
+        eprintln!("\r🚫 Tool '{}' was not run ({})", name, reason);
+    }
+    Ok(allowed)
+}
+
+impl Repl {
+    pub fn new(
+        client: ChatClient,
+        mcp_host: Option<McpHost>,
+        rag: Option<RagOptions>,
+        settings: Settings,
+        options: ReplOptions,
+        session_env: SessionEnv,
+    ) -> Self {
+        let conversation = initial_conversation();
+        let session = Session::new(session_env.clone(), conversation.clone());
+        Self {
+            client,
+            mcp_host,
+            rag,
+            settings,
+            options,
+            conversation,
+            session_env,
+            session,
+            usage: UsageTracker::default(),
+            turns: 0,
+            tee: None,
+            clipboard: Clipboard::default(),
+        }
+    }
+
+    /// Continue a saved session: its messages become the conversation and it is saved under the same id.
+    pub fn resume(&mut self, mut session: Session) {
+        let imported_from = session.env.imported_from.take();
+        session.env = SessionEnv { imported_from, ..self.session_env.clone() };
+        self.conversation = session.messages.clone();
+        self.session = session;
+    }
+
+    /// What the startup banner should mention about this chat.
+    pub fn banner_info(&self) -> BannerInfo {
+        let turns = self.conversation.iter().filter(|m| m["role"] == "user").count();
+        BannerInfo {
+            reasoning: self.client.capabilities().reasoning,
+            rag: self.rag.as_ref().map(|rag| {
+                format!("{} chunks from {} (top {} per question)", rag.store.chunks.len(), rag.store.root, rag.top_k)
+            }),
+            resumed: (turns > 0).then(|| (self.session.id.clone(), turns)),
+            saved_to: self
+                .options
+                .save
+                .then(|| (self.session.id.clone(), self.options.sessions_dir.display().to_string())),
+        }
+    }
+
+    /// Whether a turn may prompt at the terminal (picking among several choices, or approving a tool call or sampling request), so stdin must be left alone meanwhile.
+    pub fn prompts_during_turn(&self) -> bool {
+        if !self.options.interactive {
+            return false;
+        }
+        let mcp_asks = self.mcp_host.as_ref().is_some_and(|host| host.may_prompt());
+        mcp_asks || (self.options.choice_policy == ChoicePolicy::Ask && self.client.choices() > 1)
+    }
+
+    /// Input prompt label, including the optional status segment.
+    pub fn prompt(&self) -> String {
+        match usage::status_segment(&self.session_env.model, &self.usage, &self.settings) {
+            Some(status) => format!("You [{}]", status),
+            None => "You".to_string(),
+        }
+    }
+
+    /// Handle one line of input: a command or a chat turn. A failed turn is returned as `Err`.
+    pub async fn handle_line(&mut self, line: &str) -> Result<Flow> {
+        match line.trim().to_lowercase().as_str() {
+            "quit" | "exit" | "/quit" | "/exit" => {
+                println!("👋 Goodbye!");
+                return Ok(Flow::Quit);
+            }
+            "clear" | "/clear" => {
+                self.conversation = initial_conversation();
+                self.session = Session::new(self.session_env.clone(), self.conversation.clone());
+                self.usage = UsageTracker::default();
+                self.turns = 0;
+                self.client.reset_stats();
+                if let Some(host) = self.mcp_host.as_mut() {
+                    host.tool_stats.clear();
+                }
+                println!("🗑️ Conversation cleared!");
+                return Ok(Flow::Continue);
+            }
+            "/stats" => {
+                self.print_stats();
+                return Ok(Flow::Continue);
+            }
+            "" => return Ok(Flow::Continue),
+            _ => {}
+        }
+        // Commands are matched by their whole first word, so "/splitting hairs" is a chat message
+        let (command, arg) = match line.trim().split_once(char::is_whitespace) {
+            Some((command, arg)) => (command, arg.trim()),
+            None => (line.trim(), ""),
+        };
+        match command {
+            "/split" => self.split(arg)?,
+            "/checkpoint" => self.checkpoint(arg)?,
+            "/branches" => {
+                if !arg.is_empty() {
+                    anyhow::bail!("Usage: /branches");
+                }
+                for line in self.session.branches.describe(&self.conversation) {
+                    println!("{}", line);
+                }
+            }
+            "/branch" => self.branch(arg)?,
+            "/switch" => {
+                if arg.is_empty() {
+                    anyhow::bail!("Usage: /switch <name>");
+                }
+                self.session.branches.switch(arg, &mut self.conversation)?;
+                println!("🌿 Switched to branch {} ({} turns)", arg, self.user_turns());
+                self.save_session();
+            }
+            "/tee" => self.tee_command(arg)?,
+            "/sessions" => {
+                if !arg.is_empty() {
+                    anyhow::bail!("Usage: /sessions");
+                }
+                let sessions = session::list(&self.options.sessions_dir)?;
+                print!("{}", session::format_list(&sessions, Some(&self.session.id)));
+            }
+            "/copy" => self.copy(arg)?,
+            "/mcp" => self.mcp_command(arg)?,
+            _ => {
+                let result = if line.trim() == "/continue" {
+                    if self.conversation.last().is_none_or(|m| m["role"] != "assistant") {
+                        anyhow::bail!("Nothing to continue: there is no reply yet");
+                    }
+                    self.run_turn(line.trim(), true).await
+                } else {
+                    self.run_turn(line, false).await
+                };
+                if !self.options.json {
+                    println!();
+                }
+                return result.map(|_| Flow::Continue);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Send `user_input` and print the reply. With `continuing` (`/continue`), ask the model to go on
+    /// with its last reply instead, and append what it writes to that reply.
+    async fn run_turn(&mut self, user_input: &str, continuing: bool) -> Result<()> {
+        // Append user message to the conversation history
+        if !continuing {
+            self.conversation.push(serde_json::json!({"role":"user","content": user_input}));
+        }
+
+        // In --rag mode, send the question with retrieved chunks prepended; history keeps the plain question
+        let mut outgoing = self.conversation.clone();
+        // Fresh every turn; the saved history keeps the plain system prompt
+        if let Some(c) = self.options.clock {
+            clock::inject(&mut outgoing, c);
+        }
+        if continuing {
+            outgoing.push(serde_json::json!({"role":"user","content": CONTINUE_PROMPT}));
+        } else if let Some(rag) = &self.rag {
+            match self.client.embed(&rag.embedding_model, &[user_input.to_string()]).await {
+                Ok(mut vectors) if !vectors.is_empty() => {
+                    let hits = rag.store.search(&vectors.remove(0), rag.top_k);
+                    let augmented = rag::augment_prompt(user_input, &hits);
+                    if let Some(last) = outgoing.last_mut() {
+                        *last = serde_json::json!({"role":"user","content": augmented});
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("⚠️ Retrieval failed, sending question without context: {}", e),
+            }
+        }
+
+        // With MCP the tool loop does not stream, so the reply is printed once it is complete
+        let streamed = self.options.stream && self.mcp_host.is_none();
+        // Show a "thinking" indicator
+        if !self.options.json {
+            print!("🤖 Assistant: ");
+            io::stdout().flush().unwrap();
+        }
+        if !streamed && !self.options.json {
+            print!("thinking...\r");
+            io::stdout().flush().unwrap();
+        }
+
+        let result = self.complete(&outgoing, user_input).await;
+        // A streamed reply was copied as it arrived; end its entry, even when the turn failed partway
+        let copied = match self.tee.as_mut().filter(|t| t.is_open()) {
+            Some(tee) => {
+                if let Err(e) = tee.finish() {
+                    eprintln!("⚠️ {:#}", e);
+                }
+                true
+            }
+            None => false,
+        };
+        match result {
+            Ok(mut reply) => {
+                if self.options.json {
+                    let index = choice::pick(self.options.choice_policy, &reply.choices);
+                    println!("{}", choice::turn_record(user_input, &reply, index));
+                    if !reply.choices.is_empty() {
+                        reply.content = reply.choices.swap_remove(index);
+                    }
+                } else if !reply.choices.is_empty() {
+                    // Several choices: show them all and continue with the selected one
+                    println!("\r{} choices returned:        ", reply.choices.len());
+                    let selected = choice::render_and_select(&reply.choices, self.options.choice_policy, self.options.interactive);
+                    let index = match selected {
+                        Ok(i) => i,
+                        Err(e) => {
+                            if !continuing {
+                                self.conversation.pop();
+                            }
+                            return Err(e);
+                        }
+                    };
+                    reply.content = reply.choices.swap_remove(index);
+                } else if !streamed {
+                    // For non-streaming mode: clear "thinking..." and print reply
+                    if let (true, Some(reasoning)) = (self.options.show_reasoning, &reply.reasoning) {
+                        print!("\r💭 \x1b[2m{}\x1b[0m\n\n", reasoning);
+                    }
+                    print!("\r🤖 Assistant: {}\n", reply.content);
+                }
+                if reply.truncated() && !self.options.json {
+                    eprintln!("✂️ The reply was cut off at the token limit; /continue picks up where it stopped");
+                }
+                if let Some(u) = reply.usage {
+                    self.usage.record(u);
+                    self.session.usage.add(u);
+                }
+                if let Some(tee) = self.tee.as_mut().filter(|_| !copied) {
+                    if let Err(e) = tee.turn(user_input, &reply.content) {
+                        eprintln!("⚠️ {:#}", e);
+                    }
+                }
+
+                // Append assistant reply to conversation history
+                match self.conversation.last_mut() {
+                    Some(last) if continuing => {
+                        let text = format!("{}{}", last["content"].as_str().unwrap_or_default(), reply.content);
+                        last["content"] = serde_json::Value::String(text);
+                    }
+                    _ => {
+                        self.conversation.push(serde_json::json!({"role":"assistant","content": reply.content}));
+                        self.turns += 1;
+                        self.generate_title().await;
+                    }
+                }
+                self.save_session();
+                Ok(())
+            }
+            Err(e) => {
+                // On error, remove the last user message from history
+                if !continuing {
+                    self.conversation.pop();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Send the conversation, running the tool-call loop when MCP is enabled.
+    /// A streamed reply to `input` is copied to the tee as it arrives.
+    async fn complete(&mut self, outgoing: &[serde_json::Value], input: &str) -> Result<ChatReply> {
+        let Some(host) = self.mcp_host.as_mut() else {
+            return if self.options.stream {
+                let tee = &mut self.tee;
+                let mut copy = |text: &str| {
+                    if let Some(t) = tee.as_mut() {
+                        if let Err(e) = t.write(input, text) {
+                            eprintln!("\n⚠️ {:#}; stopped copying output", e);
+                            *tee = None;
+                        }
+                    }
+                };
+                self.client.send_message_streaming(outgoing, &mut copy).await
+            } else {
+                self.client.send_message(outgoing).await
+            };
+        };
+
+        let mut local_conv = outgoing.to_vec();
+        let mut round = 0;
+        // With MCP enabled, run non-streaming tool-call loop
+        let (final_text, choices, logprobs, finish_reason) = loop {
+            // Pick up tool list changes announced since the last round
+            host.process_server_messages(&self.client, self.options.interactive).await;
+            let tools: Vec<serde_json::Value> = host.tools.values().map(|(_server, desc)| {
+                serde_json::json!({
+                    "type":"function",
+                    "function":{
+                        "name": desc.name,
+                        "description": desc.description.clone().unwrap_or_default(),
+                        "parameters": desc.input_schema
+                    }
+                })
+            }).collect();
+            let resp = self.client.send_with_tools(&local_conv, &tools).await?;
+            if let Some(u) = Usage::from_json(&resp) {
+                self.usage.record(u);
+                self.session.usage.add(u);
+            }
+            let choice = &resp["choices"][0]["message"];
+            // Append assistant message (may have tool_calls)
+            local_conv.push(choice.clone());
+            if let Some(tool_calls) = choice.get("tool_calls").and_then(|v| v.as_array()) {
+                round += 1;
+                if round > self.options.max_tool_rounds {
+                    bail!(
+                        "Stopped after {} tool rounds: the model kept calling tools (raise the limit with --max-tool-rounds)",
+                        self.options.max_tool_rounds
+                    );
+                }
+                self.client.trace(&round_diagnostics(round, self.options.max_tool_rounds, tool_calls));
+                for tc in tool_calls {
+                    let id = tc["id"].as_str().unwrap_or_default();
+                    let func = &tc["function"];
+                    let name = func["name"].as_str().unwrap_or("");
+                    let args_str = func["arguments"].as_str().unwrap_or("{}");
+                    let args_json: serde_json::Value = serde_json::from_str(args_str).unwrap_or(serde_json::json!({"raw": args_str}));
+                    let server = host.tools.get(name).map(|(server, _)| server.as_str()).unwrap_or_default();
+                    let label = if server.is_empty() { name.to_string() } else { format!("{}.{}", server, name) };
+                    let show = !self.options.quiet && !self.options.json;
+                    let (tool_result, elapsed) = if approve_tool(&host.policy, server, name, &args_json, self.options.interactive)? {
+                        let call = host.call(name, args_json.clone(), &self.client, self.options.interactive);
+                        let (result, elapsed) = ui::with_spinner(&format!("calling {}", label), show, call).await;
+                        (result.unwrap_or_else(|e| serde_json::json!({"error": format!("tool call failed: {:#}", e)})), elapsed)
+                    } else {
+                        (serde_json::json!({"error": "Tool call denied by policy"}), Duration::ZERO)
+                    };
+                    if show {
+                        eprintln!("\r{}", ui::tool_summary(&label, &args_json, elapsed, &tool_result));
+                    }
+                    local_conv.push(serde_json::json!({
+                        "role":"tool",
+                        "tool_call_id": id,
+                        "content": serde_json::to_string(&tool_result).unwrap_or("null".to_string())
+                    }));
+                }
+                // Continue loop to let model consume tool outputs
+                continue;
+            } else {
+                // No tool calls; return content
+                let content = choice.get("content").and_then(|c| c.as_str()).unwrap_or("").to_string();
+                let all = resp["choices"].as_array().map(|a| a.as_slice()).unwrap_or_default();
+                let choices = if all.len() > 1 {
+                    all.iter()
+                        .map(|c| c["message"]["content"].as_str().unwrap_or("").to_string())
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                let logprobs = all.iter().map(|c| c["logprobs"].clone()).filter(|l| !l.is_null()).collect();
+                let finish_reason = resp["choices"][0]["finish_reason"].as_str().map(str::to_string);
+                break (content, choices, logprobs, finish_reason);
+            }
+        };
+
+        Ok(ChatReply { content: final_text, reasoning: None, usage: None, choices, logprobs, finish_reason })
+    }
+
+    fn user_turns(&self) -> usize {
+        self.conversation.iter().filter(|m| m["role"] == "user").count()
+    }
+
+    /// `/checkpoint <name>`: remember the conversation as it is now, to branch from later.
+    fn checkpoint(&mut self, name: &str) -> Result<()> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            anyhow::bail!("Usage: /checkpoint <name>");
+        }
+        let replaced = self.session.branches.checkpoint(name, &self.conversation);
+        let verb = if replaced { "moved" } else { "saved" };
+        println!("📌 Checkpoint {} {} at turn {}", name, verb, self.user_turns());
+        self.save_session();
+        Ok(())
+    }
+
+    /// `/branch <name> [checkpoint]`: fork a new branch from a checkpoint (or from here) and switch to it.
+    fn branch(&mut self, arg: &str) -> Result<()> {
+        let (name, from) = match arg.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [name] => (name.to_string(), None),
+            [name, from] => (name.to_string(), Some(from.to_string())),
+            _ => anyhow::bail!("Usage: /branch <name> [checkpoint]"),
+        };
+        let previous = self.session.branches.current.clone();
+        self.session.branches.branch(&name, from.as_deref(), &mut self.conversation)?;
+        let origin = from.map(|cp| format!("checkpoint {}", cp)).unwrap_or_else(|| format!("branch {}", previous));
+        println!("🌿 On new branch {} from {} ({} turns); {} is kept", name, origin, self.user_turns(), previous);
+        self.save_session();
+        Ok(())
+    }
+
+    /// `/tee <file> [all]` starts copying replies (or, with `all`, the transcript) to a file;
+    /// `/tee off` stops, and `/tee` shows where output goes.
+    fn tee_command(&mut self, arg: &str) -> Result<()> {
+        match arg.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => match &self.tee {
+                Some(tee) => println!(
+                    "📝 Copying {} to {}",
+                    if tee.transcript { "the transcript" } else { "replies" },
+                    tee.path.display()
+                ),
+                None => println!("📝 Not copying output (use /tee <file> [all])"),
+            },
+            ["off"] => {
+                if let Some(tee) = self.tee.take() {
+                    println!("📝 Stopped copying to {}", tee.path.display());
+                }
+            }
+            [path, rest @ ..] if rest.is_empty() || rest == ["all"] => {
+                let transcript = !rest.is_empty();
+                let tee = Tee::open(std::path::Path::new(path), transcript)?;
+                println!("📝 Copying {} to {}", if transcript { "the transcript" } else { "replies" }, tee.path.display());
+                self.tee = Some(tee);
+            }
+            _ => anyhow::bail!("Usage: /tee <file> [all] | /tee off"),
+        }
+        Ok(())
+    }
+
+    /// `/copy` puts the last reply on the clipboard; `/copy code [n]` its n-th fenced code block (default 1).
+    fn copy(&mut self, arg: &str) -> Result<()> {
+        let reply = self
+            .conversation
+            .iter()
+            .rev()
+            .find(|m| m["role"] == "assistant")
+            .and_then(|m| m["content"].as_str())
+            .context("No reply to copy yet")?;
+        match arg.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => {
+                self.clipboard.copy(reply)?;
+                println!("📋 Copied the last reply ({} chars)", stats::thousands(reply.chars().count() as u64));
+            }
+            ["code", rest @ ..] if rest.len() <= 1 => {
+                let n: usize = match rest.first() {
+                    Some(n) => n.parse().ok().filter(|n| *n > 0).context("Usage: /copy code [n]")?,
+                    None => 1,
+                };
+                let blocks = clipboard::code_blocks(reply);
+                let Some(block) = blocks.get(n - 1) else {
+                    match blocks.len() {
+                        0 => anyhow::bail!("The last reply has no code blocks"),
+                        len => anyhow::bail!("The last reply has only {} code block(s)", len),
+                    }
+                };
+                self.clipboard.copy(&block.code)?;
+                let lang = if block.lang.is_empty() { String::new() } else { format!("{}, ", block.lang) };
+                println!("📋 Copied code block {} of {} ({}{} lines)", n, blocks.len(), lang, block.code.lines().count());
+            }
+            _ => anyhow::bail!("Usage: /copy | /copy code [n]"),
+        }
+        Ok(())
+    }
+
+    /// `/split <turn-n>`: move turns n.. into a new session, leave a stub in the original,
+    /// and continue in the new session.
+    fn split(&mut self, arg: &str) -> Result<()> {
+        if !self.options.save {
+            anyhow::bail!("/split needs saved sessions (remove --no-save)");
+        }
+        let turn: usize = arg.parse().map_err(|_| anyhow::anyhow!("Usage: /split <turn-n>"))?;
+        let (mut kept, moved) = session::split_at_turn(&self.conversation, turn)?;
+
+        let system: Vec<_> = kept.iter().take_while(|m| m["role"] == "system").cloned().collect();
+        let mut messages = system;
+        messages.extend(moved.iter().cloned());
+        let mut new_session = Session::new(self.session.env.clone(), messages);
+        new_session.env.imported_from = None;
+        // The stub names the new session, so both ids are claimed before anything is written
+        self.session.reserve_id(&self.options.sessions_dir)?;
+        new_session.reserve_id(&self.options.sessions_dir)?;
+
+        kept.push(session::split_stub(&moved, turn, &new_session.id));
+        self.session.messages = kept;
+        self.session.save(&self.options.sessions_dir)?;
+        new_session.save(&self.options.sessions_dir)?;
+
+        println!(
+            "✂️ Moved {} turns into session {}; session {} keeps the first {} and a summary stub.",
+            moved.iter().filter(|m| m["role"] == "user").count(),
+            new_session.id,
+            self.session.id,
+            turn - 1
+        );
+        println!("Continuing in session {}.", new_session.id);
+        self.conversation = new_session.messages.clone();
+        self.session = new_session;
+        Ok(())
+    }
+
+    /// `/stats`: turns, tokens, cost, latency, and tool calls for this session.
+    fn print_stats(&self) {
+        let latency = self.client.latency_stats();
+        let total = self.usage.total;
+        println!("📊 Session {}", self.session.id);
+        println!("  Turns:        {}", self.turns);
+        let cached = self.client.cache_hits();
+        if cached > 0 {
+            println!("  API requests: {} ({} more answered from the cache)", latency.count(), cached);
+        } else {
+            println!("  API requests: {}", latency.count());
+        }
+        println!(
+            "  Tokens:       {} prompt · {} completion",
+            stats::thousands(total.prompt_tokens as u64),
+            stats::thousands(total.completion_tokens as u64)
+        );
+        if let Some(cost) = self.usage.cost(usage::price(&self.session_env.model, &self.settings)) {
+            println!("  Cost:         ${:.4}", cost);
+        }
+        match (latency.average(), latency.percentile(95)) {
+            (Some(avg), Some(p95)) => println!(
+                "  Latency:      avg {} ms · p95 {} ms",
+                stats::thousands(avg.as_millis() as u64),
+                stats::thousands(p95.as_millis() as u64)
+            ),
+            _ => println!("  Latency:      -"),
+        }
+        let tool_stats = self.mcp_host.as_ref().map(|h| &h.tool_stats).filter(|t| !t.is_empty());
+        if let Some(tool_stats) = tool_stats {
+            println!("  Tool calls:");
+            for (name, t) in tool_stats {
+                let failed = if t.errors > 0 { format!(", {} failed", t.errors) } else { String::new() };
+                println!(
+                    "    {} ×{} (avg {} ms{})",
+                    name,
+                    t.calls,
+                    (t.total_time / t.calls as u32).as_millis(),
+                    failed
+                );
+            }
+        }
+    }
+
+    /// `/mcp logs [server] [n]`: show recent stderr lines of an MCP server.
+    fn mcp_command(&self, arg: &str) -> Result<()> {
+        let host = self.mcp_host.as_ref().context("No MCP servers are running (use --mcp-config)")?;
+        let mut parts = arg.split_whitespace();
+        if parts.next() != Some("logs") {
+            anyhow::bail!("Usage: /mcp logs [server] [lines]");
+        }
+        let Some(server) = parts.next() else {
+            for (name, _) in host.servers() {
+                let log = host.log(&name);
+                let lines = log.map(|l| l.len()).unwrap_or(0);
+                let path = log.and_then(|l| l.path.as_ref()).map(|p| p.display().to_string()).unwrap_or_default();
+                println!("  {} ({} recent lines) {}", name, lines, path);
+            }
+            return Ok(());
+        };
+        let n: usize = match parts.next() {
+            Some(n) => n.parse().map_err(|_| anyhow::anyhow!("Usage: /mcp logs [server] [lines]"))?,
+            None => 50,
+        };
+        let log = host.log(server).with_context(|| format!("Unknown MCP server '{}'", server))?;
+        let lines = log.tail(n);
+        if lines.is_empty() {
+            println!("(no stderr output from {})", server);
+        }
+        for line in lines {
+            println!("[{}] {}", server, line);
+        }
+        Ok(())
+    }
+
+    /// After a failed turn that never reached the server, walk the connection step by step.
+    pub async fn diagnose_failure(&self, err: &anyhow::Error) {
+        if !net::is_connection_error(err) {
+            return;
+        }
+        println!("🔎 Could not reach the endpoint; checking the connection...");
+        let checks = net::diagnose(self.client.endpoint(), &net::ProxyEnv::detect()).await;
+        println!("{}", net::format_checks(&checks));
+    }
+
+    /// Title a saved session from its first exchange, once. Failures only show in the --verbose trace;
+    /// the next turn tries again.
+    async fn generate_title(&mut self) {
+        // Scripted runs have nobody to read the title; skip the extra request
+        if !self.options.interactive
+            || !self.options.save
+            || !self.settings.sessions.auto_title
+            || self.session.title.is_some()
+        {
+            return;
+        }
+        let Some(request) = session::title_request(&self.conversation) else {
+            return;
+        };
+        // Reasoning deployments spend part of the budget before answering
+        let max_tokens = if self.client.capabilities().reasoning { 500 } else { 20 };
+        match self.client.sample_untracked(None, &request, max_tokens, Some(0.2)).await {
+            Ok(response) => {
+                let reply = response["choices"][0]["message"]["content"].as_str().unwrap_or_default();
+                self.session.title = session::clean_title(reply);
+            }
+            Err(e) => self.client.trace(&format!("· session title failed: {:#}", e)),
+        }
+    }
+
+    fn save_session(&mut self) {
+        if !self.options.save {
+            return;
+        }
+        self.session.messages = self.conversation.clone();
+        if let Err(e) = self.session.save(&self.options.sessions_dir) {
+            eprintln!("⚠️ Failed to save session: {}", e);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+
+use crate::chat::ChatRequest;
+
+/// Responses API body (`/openai/responses`) equivalent to a Chat Completions request on `deployment`.
+///
+/// The full conversation is sent every time (`store: false`), as with Chat Completions.
+pub fn request(deployment: &str, chat: &ChatRequest) -> Value {
+    let mut body = json!({
+        "model": deployment,
+        "input": input_items(&chat.messages),
+        "store": false,
+    });
+    if let Some(tokens) = chat.max_tokens.or(chat.max_completion_tokens) {
+        body["max_output_tokens"] = json!(tokens);
+    }
+    if let Some(t) = chat.temperature {
+        body["temperature"] = json!(t);
+    }
+    if let Some(effort) = &chat.reasoning_effort {
+        body["reasoning"] = json!({"effort": effort, "summary": "auto"});
+    }
+    if let Some(tools) = &chat.tools {
+        body["tools"] = Value::Array(tools.iter().map(tool).collect());
+        body["tool_choice"] = json!("auto");
+    }
+    if chat.stream == Some(true) {
+        body["stream"] = json!(true);
+    }
+    body
+}
+
+/// Chat messages as Responses input items: tool calls and their results become
+/// `function_call` / `function_call_output` items, images become `input_image` parts.
+fn input_items(messages: &[Value]) -> Vec<Value> {
+    let mut items = Vec::new();
+    for m in messages {
+        let role = m["role"].as_str().unwrap_or("user");
+        if role == "tool" {
+            let output = match &m["content"] {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            items.push(json!({"type": "function_call_output", "call_id": m["tool_call_id"], "output": output}));
+            continue;
+        }
+        match &m["content"] {
+            Value::String(text) if !text.is_empty() => items.push(json!({"role": role, "content": text})),
+            Value::Array(parts) => {
+                let text_type = if role == "assistant" { "output_text" } else { "input_text" };
+                let parts: Vec<Value> = parts
+                    .iter()
+                    .map(|p| match p["type"].as_str() {
+                        Some("image_url") => json!({"type": "input_image", "image_url": p["image_url"]["url"]}),
+                        _ => json!({"type": text_type, "text": p["text"]}),
+                    })
+                    .collect();
+                items.push(json!({"role": role, "content": parts}));
+            }
+            _ => {}
+        }
+        for call in m["tool_calls"].as_array().into_iter().flatten() {
+            items.push(json!({
+                "type": "function_call",
+                "call_id": call["id"],
+                "name": call["function"]["name"],
+                "arguments": call["function"]["arguments"],
+            }));
+        }
+    }
+    items
+}
+
+/// Function tools are flat in the Responses API: no nested `function` object.
+fn tool(chat_tool: &Value) -> Value {
+    let f = &chat_tool["function"];
+    json!({"type": "function", "name": f["name"], "description": f["description"], "parameters": f["parameters"]})
+}
+
+/// Usage with Chat Completions field names.
+fn usage(response: &Value) -> Value {
+    let u = &response["usage"];
+    if u.is_null() {
+        return Value::Null;
+    }
+    json!({
+        "prompt_tokens": u["input_tokens"].as_u64().unwrap_or(0),
+        "completion_tokens": u["output_tokens"].as_u64().unwrap_or(0),
+        "total_tokens": u["total_tokens"].as_u64().unwrap_or(0),
+    })
+}
+
+/// Chat Completions shape of a Responses result, so replies and tool calls are handled the same way for both APIs.
+pub fn to_chat_completion(response: &Value) -> Result<Value> {
+    if let Some(message) = response["error"]["message"].as_str() {
+        bail!("Response failed: {}", message);
+    }
+    let mut content = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls = Vec::new();
+    for item in response["output"].as_array().into_iter().flatten() {
+        match item["type"].as_str() {
+            Some("message") => {
+                for part in item["content"].as_array().into_iter().flatten() {
+                    if part["type"] == "output_text" {
+                        content.push_str(part["text"].as_str().unwrap_or_default());
+                    }
+                }
+            }
+            Some("reasoning") => {
+                for part in item["summary"].as_array().into_iter().flatten() {
+                    reasoning.push_str(part["text"].as_str().unwrap_or_default());
+                }
+            }
+            Some("function_call") => tool_calls.push(json!({
+                "id": item["call_id"],
+                "type": "function",
+                "function": {"name": item["name"], "arguments": item["arguments"]},
+            })),
+            _ => {}
+        }
+    }
+    let mut message = json!({"role": "assistant", "content": content});
+    if !reasoning.is_empty() {
+        message["reasoning_content"] = json!(reasoning);
+    }
+    let finish_reason = if !tool_calls.is_empty() {
+        message["tool_calls"] = Value::Array(tool_calls);
+        "tool_calls"
+    } else if response["incomplete_details"]["reason"] == "max_output_tokens" {
+        "length"
+    } else {
+        "stop"
+    };
+    Ok(json!({
+        "model": response["model"],
+        "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}],
+        "usage": usage(response),
+    }))
+}
+
+/// One event of a Responses stream, as the equivalent Chat Completions chunk.
+/// `Ok(None)` for events without text; an error for `response.failed` and `error` events.
+pub fn to_chat_chunk(data: &str) -> Result<Option<Value>> {
+    let Ok(event) = serde_json::from_str::<Value>(data) else {
+        return Ok(None);
+    };
+    let delta = |key: &str| Some(json!({"choices": [{"delta": {key: event["delta"]}}]}));
+    Ok(match event["type"].as_str() {
+        Some("response.output_text.delta") => delta("content"),
+        Some("response.reasoning_summary_text.delta") => delta("reasoning_content"),
+        Some("response.completed") => Some(json!({"choices": [], "usage": usage(&event["response"])})),
+        Some("response.incomplete") => {
+            let reason = match event["response"]["incomplete_details"]["reason"].as_str() {
+                Some("max_output_tokens") => "length",
+                _ => "stop",
+            };
+            Some(json!({"choices": [{"delta": {}, "finish_reason": reason}], "usage": usage(&event["response"])}))
+        }
+        Some("response.failed") => {
+            let message = event["response"]["error"]["message"].as_str().unwrap_or("unknown error");
+            bail!("Response failed: {}", message)
+        }
+        Some("error") => bail!("Response failed: {}", event["message"].as_str().unwrap_or("unknown error")),
+        _ => None,
+    })
+}
+
+/// Whether a stream event ends the response (there is no `[DONE]` line).
+pub fn is_final(data: &str) -> bool {
+    serde_json::from_str::<Value>(data)
+        .is_ok_and(|e| matches!(e["type"].as_str(), Some("response.completed" | "response.incomplete")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_conversation_and_tools() {
+        let chat = ChatRequest {
+            messages: vec![
+                json!({"role": "system", "content": "Be brief."}),
+                json!({"role": "user", "content": "Add 1 and 2"}),
+                json!({"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "c1", "type": "function", "function": {"name": "add", "arguments": "{\"a\":1,\"b\":2}"}}
+                ]}),
+                json!({"role": "tool", "tool_call_id": "c1", "content": "3"}),
+            ],
+            max_tokens: Some(1000),
+            temperature: Some(0.7),
+            tools: Some(vec![json!({"type": "function", "function": {"name": "add", "description": "Add", "parameters": {}}})]),
+            stream: Some(false),
+            ..Default::default()
+        };
+        let body = request("gpt-4o", &chat);
+        assert_eq!(body["model"], "gpt-4o");
+        assert_eq!(body["max_output_tokens"], 1000);
+        assert_eq!(body["input"][1], json!({"role": "user", "content": "Add 1 and 2"}));
+        assert_eq!(body["input"][2]["type"], "function_call");
+        assert_eq!(body["input"][2]["call_id"], "c1");
+        assert_eq!(body["input"][3], json!({"type": "function_call_output", "call_id": "c1", "output": "3"}));
+        assert_eq!(body["tools"][0]["name"], "add");
+        assert!(body.get("stream").is_none());
+    }
+
+    #[test]
+    fn converts_output_items() {
+        let response = json!({
+            "model": "gpt-4o",
+            "output": [
+                {"type": "message", "content": [{"type": "output_text", "text": "Hi"}]},
+                {"type": "function_call", "call_id": "c2", "name": "echo", "arguments": "{}"}
+            ],
+            "usage": {"input_tokens": 10, "output_tokens": 5, "total_tokens": 15}
+        });
+        let v = to_chat_completion(&response).unwrap();
+        assert_eq!(v["choices"][0]["message"]["content"], "Hi");
+        assert_eq!(v["choices"][0]["message"]["tool_calls"][0]["function"]["name"], "echo");
+        assert_eq!(v["usage"]["total_tokens"], 15);
+
+        let chunk = to_chat_chunk(r#"{"type":"response.output_text.delta","delta":"Hel"}"#).unwrap().unwrap();
+        assert_eq!(chunk["choices"][0]["delta"]["content"], "Hel");
+        assert!(to_chat_chunk(r#"{"type":"response.created"}"#).unwrap().is_none());
+        assert!(to_chat_chunk(r#"{"type":"response.failed","response":{"error":{"message":"x"}}}"#).is_err());
+        assert!(is_final(r#"{"type":"response.completed","response":{}}"#));
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{anyhow, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use std::fs;
+
+use crate::repl::{Flow, Repl};
+
+/// Parse a command file into REPL inputs.
+///
+/// One input per line; blank lines and lines starting with `#` are skipped.
+/// A line `<<TAG` starts a here-doc: following lines up to a line that is exactly `TAG`
+/// are sent as a single multi-line input.
+pub fn parse_script(text: &str) -> Result<Vec<String>> {
+    let mut inputs = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((n, line)) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(tag) = trimmed.strip_prefix("<<") {
+            let tag = tag.trim();
+            if tag.is_empty() {
+                return Err(anyhow!("line {}: here-doc needs a terminator, e.g. <<EOF", n + 1));
+            }
+            let mut body = Vec::new();
+            let mut closed = false;
+            for (_, l) in lines.by_ref() {
+                if l.trim_end() == tag {
+                    closed = true;
+                    break;
+                }
+                body.push(l);
+            }
+            if !closed {
+                return Err(anyhow!("line {}: here-doc <<{} is not terminated", n + 1, tag));
+            }
+            inputs.push(body.join("\n"));
+        } else {
+            inputs.push(trimmed.to_string());
+        }
+    }
+    Ok(inputs)
+}
+
+/// Run every input from `path` through the REPL.
+/// A failed turn aborts the script unless `pause_on_error` is set, in which case the user decides.
+pub async fn run_script(repl: &mut Repl, path: &str, pause_on_error: bool) -> Result<()> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read script {}", path))?;
+    let inputs = parse_script(&text).with_context(|| format!("Invalid script {}", path))?;
+
+    for (i, input) in inputs.iter().enumerate() {
+        if !repl.options.json {
+            println!("{}: {}", repl.prompt(), input);
+        }
+        match repl.handle_line(input).await {
+            Ok(Flow::Quit) => break,
+            Ok(Flow::Continue) => {}
+            Err(e) => {
+                if !pause_on_error {
+                    repl.diagnose_failure(&e).await;
+                    return Err(e.context(format!("Script stopped at input {} of {}", i + 1, inputs.len())));
+                }
+                println!("\r❌ Error: {}", e);
+                repl.diagnose_failure(&e).await;
+                let keep_going = Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Continue with the rest of the script?")
+                    .default(true)
+                    .interact()
+                    .context("Failed to read confirmation")?;
+                if !keep_going {
+                    return Err(e.context(format!("Script aborted at input {} of {}", i + 1, inputs.len())));
+                }
+            }
+        }
+    }
+    let hits = repl.client.cache_hits();
+    if hits > 0 {
+        eprintln!("💾 {} repeated request(s) answered from the cache (--no-cache to disable)", hits);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines_comments_and_heredocs() {
+        let text = "# demo\nhello\n\n/clear\n<<EOF\nline one\n  line two\nEOF\nquit\n";
+        assert_eq!(
+            parse_script(text).unwrap(),
+            vec!["hello", "/clear", "line one\n  line two", "quit"]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_heredoc() {
+        assert!(parse_script("<<END\nno end").is_err());
+    }
+}
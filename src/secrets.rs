@@ -0,0 +1,18 @@
+use anyhow::{Context, Result};
+
+/// Service name under which secrets are stored in the system keyring.
+pub const KEYRING_SERVICE: &str = "rust-openai-chat";
+
+/// Read a secret stored under `name` in the system keyring.
+pub fn get(name: &str) -> Result<String> {
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .and_then(|e| e.get_password())
+        .with_context(|| format!("Failed to read '{}' from the system keyring", name))
+}
+
+/// Store a secret under `name` in the system keyring.
+pub fn set(name: &str, value: &str) -> Result<()> {
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .and_then(|e| e.set_password(value))
+        .with_context(|| format!("Failed to store '{}' in the system keyring", name))
+}
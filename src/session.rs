@@ -0,0 +1,372 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::branch::Branches;
+use crate::mcp::client::McpServerInfo;
+use crate::stats::thousands;
+use crate::usage::Usage;
+
+/// A saved conversation plus the environment it was produced in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+    /// Short title written by the model after the first turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Tokens used by the conversation's requests, including earlier runs of a resumed session.
+    #[serde(default)]
+    pub usage: Usage,
+    pub env: SessionEnv,
+    /// Messages of the active branch.
+    pub messages: Vec<serde_json::Value>,
+    /// Other branches and checkpoints made with /branch and /checkpoint.
+    #[serde(default, skip_serializing_if = "Branches::is_empty")]
+    pub branches: Branches,
+    /// Whether `id` names a file this session owns: it was loaded, or `reserve_id` created it.
+    #[serde(skip)]
+    reserved: bool,
+}
+
+/// Everything needed to interpret an old transcript later.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionEnv {
+    pub cli_version: String,
+    pub model: String,
+    pub api_version: String,
+    #[serde(default)]
+    pub mcp_servers: Vec<SessionMcpServer>,
+    /// Request parameters as sent to the API (e.g. max_tokens, temperature, reasoning_effort).
+    #[serde(default)]
+    pub params: BTreeMap<String, serde_json::Value>,
+    /// Source file when the session was created by `import` rather than a live chat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imported_from: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMcpServer {
+    pub name: String,
+    #[serde(default)]
+    pub info: Option<McpServerInfo>,
+}
+
+impl Session {
+    pub fn new(env: SessionEnv, messages: Vec<serde_json::Value>) -> Self {
+        let now = Local::now();
+        Self {
+            id: now.format("%Y%m%d-%H%M%S").to_string(),
+            created_at: now,
+            updated_at: now,
+            title: None,
+            usage: Usage::default(),
+            env,
+            messages,
+            branches: Branches::default(),
+            reserved: false,
+        }
+    }
+
+    pub fn load(dir: &Path, id: &str) -> Result<Self> {
+        let path = dir.join(format!("{}.json", id));
+        let s = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read session {}", path.display()))?;
+        let mut session: Self =
+            serde_json::from_str(&s).with_context(|| format!("Invalid session file {}", path.display()))?;
+        session.reserved = true;
+        Ok(session)
+    }
+
+    /// Claim a file in `dir` for a new session. Ids are timestamps to the second, so a session started in the
+    /// same second as an existing one gets `-2`, `-3`, ... appended. Later calls keep the claimed id.
+    pub fn reserve_id(&mut self, dir: &Path) -> Result<()> {
+        if self.reserved {
+            return Ok(());
+        }
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create sessions directory {}", dir.display()))?;
+        let base = self.id.clone();
+        let mut n = 1;
+        loop {
+            let path = dir.join(format!("{}.json", self.id));
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => break,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    n += 1;
+                    self.id = format!("{}-{}", base, n);
+                }
+                Err(e) => return Err(e).with_context(|| format!("Failed to create session {}", path.display())),
+            }
+        }
+        self.reserved = true;
+        Ok(())
+    }
+
+    pub fn save(&mut self, dir: &Path) -> Result<()> {
+        self.reserve_id(dir)?;
+        self.updated_at = Local::now();
+        let path = dir.join(format!("{}.json", self.id));
+        let s = serde_json::to_string_pretty(self)?;
+        fs::write(&path, s).with_context(|| format!("Failed to write session {}", path.display()))
+    }
+}
+
+/// Every readable session in `dir`, most recently updated first.
+pub fn list(dir: &Path) -> Result<Vec<Session>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read sessions directory {}", dir.display())),
+    };
+    let mut sessions: Vec<Session> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|x| x == "json"))
+        .filter_map(|e| serde_json::from_str(&fs::read_to_string(e.path()).ok()?).ok())
+        .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+    Ok(sessions)
+}
+
+/// Table shown by `sessions list` and `/sessions`; `current` is marked with `*`.
+pub fn format_list(sessions: &[Session], current: Option<&str>) -> String {
+    if sessions.is_empty() {
+        return "No saved sessions yet.\n".to_string();
+    }
+    let mut out = format!("  {:<15}  {:<16}  {:>5}  {:>8}  {}\n", "ID", "Updated", "Turns", "Tokens", "Title");
+    for s in sessions {
+        let marker = if current == Some(s.id.as_str()) { "*" } else { " " };
+        let turns = s.messages.iter().filter(|m| m["role"] == "user").count();
+        // Untitled sessions (imported, or saved before titles existed) show their first prompt instead
+        let title = s.title.clone().unwrap_or_else(|| {
+            let first = s.messages.iter().find(|m| m["role"] == "user").and_then(|m| m["content"].as_str());
+            let line = first.and_then(|t| t.lines().next()).unwrap_or("");
+            let mut preview: String = line.chars().take(50).collect();
+            if line.chars().count() > 50 {
+                preview.push('…');
+            }
+            format!("\"{}\"", preview)
+        });
+        out.push_str(&format!(
+            "{} {:<15}  {:<16}  {:>5}  {:>8}  {}\n",
+            marker,
+            s.id,
+            s.updated_at.format("%Y-%m-%d %H:%M"),
+            turns,
+            thousands(s.usage.total_tokens as u64),
+            title
+        ));
+    }
+    out
+}
+
+/// Request asking the model to title a conversation from its first exchange, or `None` before there is one.
+pub fn title_request(messages: &[serde_json::Value]) -> Option<Vec<serde_json::Value>> {
+    let text = |role: &str| {
+        let content = messages.iter().find(|m| m["role"] == role)?["content"].as_str()?;
+        Some(content.chars().take(1000).collect::<String>())
+    };
+    let (question, answer) = (text("user")?, text("assistant")?);
+    Some(vec![
+        serde_json::json!({"role": "system", "content": "Write a title of at most six words for this conversation. Reply with the title only."}),
+        serde_json::json!({"role": "user", "content": format!("User: {}\n\nAssistant: {}", question, answer)}),
+    ])
+}
+
+/// The model's title reply tidied up: one line, no quotes or trailing period, at most 60 characters.
+pub fn clean_title(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line.strip_prefix("Title:").unwrap_or(line).trim();
+    let line = line.trim_matches(|c| matches!(c, '"' | '\'' | '“' | '”' | '*' | '#')).trim().trim_end_matches('.');
+    let title: String = line.chars().take(60).collect();
+    (!title.is_empty()).then_some(title)
+}
+
+/// Default location for saved sessions: `<data dir>/rust-openai-chat/sessions`.
+pub fn default_sessions_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("rust-openai-chat")
+        .join("sessions")
+}
+
+/// Render the environment block shown by `sessions show --env`.
+pub fn format_env(session: &Session) -> String {
+    let env = &session.env;
+    let mut out = String::new();
+    out.push_str(&format!("Session:     {}\n", session.id));
+    out.push_str(&format!("Created:     {}\n", session.created_at.format("%Y-%m-%d %H:%M:%S %:z")));
+    out.push_str(&format!("CLI version: {}\n", env.cli_version));
+    if let Some(src) = &env.imported_from {
+        out.push_str(&format!("Imported:    {}\n", src));
+    }
+    out.push_str(&format!("Model:       {}\n", env.model));
+    out.push_str(&format!("API version: {}\n", env.api_version));
+    out.push_str("Parameters:\n");
+    if env.params.is_empty() {
+        out.push_str("  (none recorded)\n");
+    }
+    for (k, v) in &env.params {
+        out.push_str(&format!("  {} = {}\n", k, v));
+    }
+    out.push_str("MCP servers:\n");
+    if env.mcp_servers.is_empty() {
+        out.push_str("  (none)\n");
+    }
+    for server in &env.mcp_servers {
+        match &server.info {
+            Some(info) => out.push_str(&format!(
+                "  {} ({} {}, protocol {})\n",
+                server.name,
+                info.name,
+                info.version.as_deref().unwrap_or("?"),
+                info.protocol_version.as_deref().unwrap_or("?")
+            )),
+            None => out.push_str(&format!("  {} (not initialized)\n", server.name)),
+        }
+    }
+    out
+}
+
+/// Split messages at the start of user turn `turn` (1-based), returning `(kept, moved)`.
+/// Leading system messages stay in `kept`; `moved` starts with that turn's user message.
+pub fn split_at_turn(
+    messages: &[serde_json::Value],
+    turn: usize,
+) -> Result<(Vec<serde_json::Value>, Vec<serde_json::Value>)> {
+    let starts: Vec<usize> =
+        messages.iter().enumerate().filter(|(_, m)| m["role"] == "user").map(|(i, _)| i).collect();
+    if turn == 0 || turn > starts.len() {
+        anyhow::bail!("Turn {} does not exist (this session has {} turns)", turn, starts.len());
+    }
+    if turn == 1 {
+        anyhow::bail!("Splitting at turn 1 would move the whole conversation; use 'clear' instead");
+    }
+    let at = starts[turn - 1];
+    Ok((messages[..at].to_vec(), messages[at..].to_vec()))
+}
+
+/// Stub left in the original session in place of turns moved out by `/split`.
+pub fn split_stub(moved: &[serde_json::Value], first_turn: usize, new_id: &str) -> serde_json::Value {
+    let prompts: Vec<&str> = moved.iter().filter(|m| m["role"] == "user").filter_map(|m| m["content"].as_str()).collect();
+    let last_turn = first_turn + prompts.len() - 1;
+    let mut text = if last_turn == first_turn {
+        format!("[Turn {} was moved to session {}. It covered:]\n", first_turn, new_id)
+    } else {
+        format!("[Turns {}-{} were moved to session {}. They covered:]\n", first_turn, last_turn, new_id)
+    };
+    for p in prompts {
+        let line = p.lines().next().unwrap_or("");
+        let short: String = line.chars().take(80).collect();
+        let ellipsis = if short.len() < line.len() || p.lines().nth(1).is_some() { "…" } else { "" };
+        text.push_str(&format!("- {}{}\n", short, ellipsis));
+    }
+    serde_json::json!({"role": "assistant", "content": text.trim_end()})
+}
+
+/// Render the transcript shown by `sessions show`.
+pub fn format_transcript(session: &Session) -> String {
+    let mut out = String::new();
+    for m in &session.messages {
+        let role = m["role"].as_str().unwrap_or("?");
+        let content = m["content"].as_str().unwrap_or("");
+        if role == "system" || content.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("[{}] {}\n\n", role, content));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("rustcli-session-test-{}", std::process::id()));
+        let mut env = SessionEnv { model: "gpt-4o".into(), ..Default::default() };
+        env.params.insert("temperature".into(), serde_json::json!(0.7));
+        let mut session = Session::new(env, vec![serde_json::json!({"role":"user","content":"hi"})]);
+        session.save(&dir).unwrap();
+
+        let loaded = Session::load(&dir, &session.id).unwrap();
+        assert_eq!(loaded.env.model, "gpt-4o");
+        assert_eq!(loaded.messages.len(), 1);
+        assert!(format_env(&loaded).contains("temperature = 0.7"));
+
+        let listed = list(&dir).unwrap();
+        assert_eq!(listed.len(), 1);
+        let table = format_list(&listed, Some(&session.id));
+        assert!(table.lines().nth(1).unwrap().starts_with(&format!("* {}", session.id)));
+        assert!(table.contains("\"hi\""));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn sessions_started_in_the_same_second_get_distinct_ids() {
+        let dir = std::env::temp_dir().join(format!("rustcli-session-ids-{}", std::process::id()));
+        let mut first = Session::new(SessionEnv::default(), Vec::new());
+        let mut second = first.clone();
+        let mut third = first.clone();
+        first.save(&dir).unwrap();
+        second.save(&dir).unwrap();
+        third.reserve_id(&dir).unwrap();
+        assert_eq!(second.id, format!("{}-2", first.id));
+        assert_eq!(third.id, format!("{}-3", first.id));
+
+        // Saving again, or saving a loaded session, keeps the file it already owns
+        second.save(&dir).unwrap();
+        assert_eq!(second.id, format!("{}-2", first.id));
+        let mut loaded = Session::load(&dir, &first.id).unwrap();
+        loaded.save(&dir).unwrap();
+        assert_eq!(loaded.id, first.id);
+        assert_eq!(list(&dir).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn builds_and_cleans_titles() {
+        let msgs = vec![
+            serde_json::json!({"role":"system","content":"sys"}),
+            serde_json::json!({"role":"user","content":"How do lifetimes work?"}),
+        ];
+        assert!(title_request(&msgs).is_none());
+        let mut msgs = msgs;
+        msgs.push(serde_json::json!({"role":"assistant","content":"They describe scopes."}));
+        let request = title_request(&msgs).unwrap();
+        assert!(request[1]["content"].as_str().unwrap().contains("Assistant: They describe scopes."));
+
+        assert_eq!(clean_title("\"Rust Lifetimes Explained.\"\n").as_deref(), Some("Rust Lifetimes Explained"));
+        assert_eq!(clean_title("Title: Trip planning").as_deref(), Some("Trip planning"));
+        assert_eq!(clean_title("  \n"), None);
+    }
+
+    #[test]
+    fn splits_at_user_turns_and_stubs_the_rest() {
+        let msgs = vec![
+            serde_json::json!({"role":"system","content":"sys"}),
+            serde_json::json!({"role":"user","content":"rust question"}),
+            serde_json::json!({"role":"assistant","content":"a1"}),
+            serde_json::json!({"role":"user","content":"trip planning\nmore detail"}),
+            serde_json::json!({"role":"assistant","content":"a2"}),
+            serde_json::json!({"role":"user","content":"hotels"}),
+        ];
+        let (kept, moved) = split_at_turn(&msgs, 2).unwrap();
+        assert_eq!(kept.len(), 3);
+        assert_eq!(moved[0]["content"], "trip planning\nmore detail");
+        assert!(split_at_turn(&msgs, 1).is_err());
+        assert!(split_at_turn(&msgs, 4).is_err());
+
+        let stub = split_stub(&moved, 2, "20250101-093000");
+        let text = stub["content"].as_str().unwrap();
+        assert!(text.starts_with("[Turns 2-3 were moved to session 20250101-093000"));
+        assert!(text.contains("- trip planning…\n- hotels"));
+    }
+}
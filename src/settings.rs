@@ -0,0 +1,276 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::secrets;
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Application settings loaded from `config.yaml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    /// Azure OpenAI endpoint, used when neither --endpoint nor OPENAI_API_ENDPOINT is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// API key in plain text. Prefer `api_key_in_keyring`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Read the API key from the system keyring (service `rust-openai-chat`, entry `api-key`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub api_key_in_keyring: bool,
+    /// Default deployment name, used when --model / OPENAI_API_MODEL is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Default api-version, used when --api-version / OPENAI_API_VERSION is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// Presentation options.
+    #[serde(default)]
+    pub theme: Theme,
+    /// Per-model prices in USD per 1K tokens, overriding the built-in table.
+    #[serde(default)]
+    pub pricing: HashMap<String, ModelPrice>,
+    /// Per-model context window sizes in tokens, overriding the built-in table.
+    #[serde(default)]
+    pub context_windows: HashMap<String, u32>,
+    /// Per-model average characters per token, overriding the built-in table (4 for OpenAI models).
+    #[serde(default)]
+    pub chars_per_token: HashMap<String, f32>,
+    /// Date/time injection into the system prompt.
+    #[serde(default)]
+    pub clock: ClockSettings,
+    /// What is printed when an interactive chat starts.
+    #[serde(default)]
+    pub greeting: Greeting,
+    /// Client-side limits matching the deployment's quota.
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+    /// Saved-session options.
+    #[serde(default)]
+    pub sessions: SessionSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSettings {
+    /// Ask the model for a short title after the first turn of a saved session.
+    #[serde(default = "default_true")]
+    pub auto_title: bool,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self { auto_title: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateLimitSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Estimated as about 4 characters per prompt token plus `max_tokens`, as Azure counts them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Greeting {
+    /// Print the startup banner at all (`--no-banner` turns it off too).
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Replaces the "🤖 Azure OpenAI Chat CLI" title line; may span several lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// The "Type 'quit' ..." hint lines.
+    #[serde(default = "default_true")]
+    pub hints: bool,
+    /// Line printed under the banner; empty for none.
+    #[serde(default = "default_separator")]
+    pub separator: String,
+}
+
+impl Default for Greeting {
+    fn default() -> Self {
+        Self { enabled: true, text: None, hints: true, separator: default_separator() }
+    }
+}
+
+fn default_separator() -> String {
+    "=".repeat(50)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClockSettings {
+    /// Add the current date, time, and timezone to the system prompt on every turn.
+    #[serde(default)]
+    pub inject: bool,
+    /// `local` (default), `UTC`, or a fixed offset such as `+02:00`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Theme {
+    /// Status segment rendered in the input prompt.
+    #[serde(default)]
+    pub status: StatusLine,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusLine {
+    /// Render the status segment at all. Off by default.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub model: bool,
+    #[serde(default = "default_true")]
+    pub context: bool,
+    #[serde(default = "default_true")]
+    pub cost: bool,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self { enabled: false, model: true, context: true, cost: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// USD per 1K prompt tokens.
+    pub input: f64,
+    /// USD per 1K completion tokens.
+    pub output: f64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Keyring entry of the API key when `api_key_in_keyring` is set. A fixed name, so the key is found
+/// whichever way the endpoint is given (config file, --endpoint, OPENAI_API_ENDPOINT).
+pub const API_KEY_ENTRY: &str = "api-key";
+
+impl Settings {
+    /// Load settings from an explicit path, or from the default location if it exists.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(p) => (PathBuf::from(p), true),
+            None => (default_config_path(), false),
+        };
+        if !required && !path.exists() {
+            return Ok(Self::default());
+        }
+        let s = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config from {}", path.display()))?;
+        let settings: Settings = serde_yaml::from_str(&s)
+            .with_context(|| format!("Invalid config YAML in {}", path.display()))?;
+        Ok(settings)
+    }
+
+    /// API key from the config file or, when `api_key_in_keyring` is set, from the system keyring.
+    pub fn resolve_api_key(&self) -> Option<String> {
+        self.resolve_api_key_with(&|name| secrets::get(name))
+    }
+
+    fn resolve_api_key_with(&self, get: &dyn Fn(&str) -> Result<String>) -> Option<String> {
+        if self.api_key_in_keyring {
+            match get(API_KEY_ENTRY) {
+                Ok(key) => return Some(key),
+                Err(e) => eprintln!("⚠️ {:#}", e),
+            }
+        }
+        self.api_key.clone()
+    }
+
+    /// Keep `key` in the system keyring when `in_keyring` is set and that works, otherwise in the config file.
+    pub fn set_api_key(&mut self, key: String, in_keyring: bool) {
+        self.set_api_key_with(key, in_keyring, &secrets::set)
+    }
+
+    fn set_api_key_with(&mut self, key: String, in_keyring: bool, store: &dyn Fn(&str, &str) -> Result<()>) {
+        if in_keyring {
+            match store(API_KEY_ENTRY, &key) {
+                Ok(()) => {
+                    self.api_key = None;
+                    self.api_key_in_keyring = true;
+                    return;
+                }
+                Err(e) => eprintln!("⚠️ {:#}; storing the key in the config file instead.", e),
+            }
+        }
+        self.api_key = Some(key);
+        self.api_key_in_keyring = false;
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory {}", parent.display()))?;
+        }
+        let s = serde_yaml::to_string(self)?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // The file may hold the API key: readable by the owner only
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("Failed to write config to {}", path.display()))?;
+        // `mode` only applies to new files; tighten one written by an earlier version too
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict permissions of {}", path.display()))?;
+        file.write_all(s.as_bytes())
+            .with_context(|| format!("Failed to write config to {}", path.display()))
+    }
+}
+
+/// Default config location: `<config dir>/rust-openai-chat/config.yaml`.
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("rust-openai-chat")
+        .join("config.yaml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn api_key_round_trips_through_the_keyring() {
+        let store = RefCell::new(HashMap::new());
+        let mut settings = Settings { endpoint: Some("https://res.openai.azure.com".into()), ..Default::default() };
+        settings.set_api_key_with("secret".into(), true, &|name, value| {
+            store.borrow_mut().insert(name.to_string(), value.to_string());
+            Ok(())
+        });
+        assert!(settings.api_key_in_keyring);
+        assert_eq!(settings.api_key, None);
+        // Read back from the same entry, whatever endpoint is in use by then
+        let get = |name: &str| store.borrow().get(name).cloned().context("missing");
+        assert_eq!(settings.resolve_api_key_with(&get).as_deref(), Some("secret"));
+
+        settings.set_api_key_with("plain".into(), true, &|_, _| anyhow::bail!("no keyring"));
+        assert!(!settings.api_key_in_keyring);
+        assert_eq!(settings.resolve_api_key_with(&get).as_deref(), Some("plain"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn config_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("rust-openai-chat-settings-{}.yaml", std::process::id()));
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let settings = Settings { api_key: Some("secret".into()), ..Default::default() };
+        settings.save(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
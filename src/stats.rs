@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, time::Duration};
+
+/// Latency of completed API requests.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples: Vec<Duration>,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, elapsed: Duration) {
+        self.samples.push(elapsed);
+    }
+
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn average(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<Duration>() / self.samples.len() as u32)
+    }
+
+    /// Nearest-rank percentile, e.g. `percentile(95)`.
+    pub fn percentile(&self, p: usize) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = (p * sorted.len()).div_ceil(100).max(1);
+        Some(sorted[rank - 1])
+    }
+}
+
+/// Calls and failures of one MCP tool.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ToolStats {
+    pub calls: usize,
+    pub errors: usize,
+    pub total_time: Duration,
+}
+
+/// Per-tool counters, keyed by tool name.
+pub type ToolStatsMap = BTreeMap<String, ToolStats>;
+
+/// `1234567` → `1,234,567`.
+pub fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_and_percentiles() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.percentile(95), None);
+        for ms in 1..=20 {
+            stats.record(Duration::from_millis(ms * 100));
+        }
+        assert_eq!(stats.average(), Some(Duration::from_millis(1050)));
+        assert_eq!(stats.percentile(95), Some(Duration::from_millis(1900)));
+        assert_eq!(stats.percentile(50), Some(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn groups_thousands() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(1234), "1,234");
+        assert_eq!(thousands(1234567), "1,234,567");
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Copy of assistant replies (or the whole transcript) appended to a file, for `--output` and `/tee`.
+pub struct Tee {
+    file: File,
+    pub path: PathBuf,
+    /// Also write user inputs, not just replies.
+    pub transcript: bool,
+    /// A reply is being written and has not been finished yet.
+    open: bool,
+}
+
+impl Tee {
+    pub fn open(path: &Path, transcript: bool) -> Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open output file {}", path.display()))?;
+        Ok(Self { file, path: path.to_path_buf(), transcript, open: false })
+    }
+
+    /// Append one turn, without terminal escape codes.
+    pub fn turn(&mut self, input: &str, reply: &str) -> Result<()> {
+        self.write(input, reply)?;
+        self.finish()
+    }
+
+    /// Append part of the reply to `input` as it streams in, starting the entry first if needed.
+    pub fn write(&mut self, input: &str, text: &str) -> Result<()> {
+        let mut chunk = String::new();
+        if !self.open && self.transcript {
+            chunk.push_str(&format!("You: {}\n\nAssistant: ", input));
+        }
+        self.open = true;
+        chunk.push_str(text);
+        self.append(&chunk)
+    }
+
+    /// End the reply being written, if any (also after a failed or interrupted turn).
+    pub fn finish(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.open) {
+            return Ok(());
+        }
+        self.append("\n\n")
+    }
+
+    /// Whether part of a reply has been written since the last `finish`.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn append(&mut self, text: &str) -> Result<()> {
+        self.file
+            .write_all(strip_ansi(text).as_bytes())
+            .and_then(|_| self.file.flush())
+            .with_context(|| format!("Failed to write to {}", self.path.display()))
+    }
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement, OSC titles/links) and carriage returns.
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters, then a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: ends with BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_escape_codes() {
+        assert_eq!(strip_ansi("\x1b[2mthinking\x1b[0m done"), "thinking done");
+        assert_eq!(strip_ansi("\x1b]8;;https://x.y\x1b\\link\x1b]8;;\x07!"), "link!");
+        assert_eq!(strip_ansi("\rplain text\n"), "plain text\n");
+    }
+
+    #[test]
+    fn writes_replies_as_they_stream() {
+        let path = std::env::temp_dir().join(format!("rust-openai-chat-tee-{}.txt", std::process::id()));
+        let mut tee = Tee::open(&path, true).unwrap();
+        tee.write("hi", "Hel").unwrap();
+        tee.write("hi", "\x1b[1mlo\x1b[0m").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "You: hi\n\nAssistant: Hello");
+        tee.finish().unwrap();
+        tee.finish().unwrap();
+        tee.turn("again", "Hi").unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(text, "You: hi\n\nAssistant: Hello\n\nYou: again\n\nAssistant: Hi\n\n");
+    }
+}
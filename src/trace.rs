@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+/// Response headers worth showing when debugging quota, routing, and support cases.
+const TRACED_HEADERS: &[&str] = &[
+    "x-ratelimit-remaining-requests",
+    "x-ratelimit-remaining-tokens",
+    "x-ratelimit-limit-requests",
+    "x-ratelimit-limit-tokens",
+    "retry-after",
+    "x-request-id",
+    "apim-request-id",
+    "x-ms-region",
+    "x-ms-deployment-name",
+];
+
+/// Request/response tracing for `--verbose`.
+///
+/// Level 1 logs each request line, response status, latency, and rate-limit headers.
+/// Level 2 adds the full request JSON, response bodies, and raw SSE lines.
+/// The API key is never written out in full.
+pub struct Tracer {
+    pub level: u8,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Tracer {
+    /// Trace to `file` (appending) when given, otherwise to stderr.
+    pub fn new(level: u8, file: Option<&Path>) -> Result<Self> {
+        let out: Box<dyn Write + Send> = match file {
+            Some(path) => Box::new(
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open trace file {}", path.display()))?,
+            ),
+            None => Box::new(io::stderr()),
+        };
+        Ok(Self { level, out: Mutex::new(out) })
+    }
+
+    fn write(&self, text: &str) {
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(out, "{}", text);
+        let _ = out.flush();
+    }
+
+    pub fn request(&self, url: &str, api_key: &str, body: &impl serde::Serialize) {
+        self.write(&format!("→ POST {}\n  api-key: {}", url, redact_key(api_key)));
+        if self.level >= 2 {
+            self.write(&indent(&serde_json::to_string_pretty(body).unwrap_or_default()));
+        }
+    }
+
+    pub fn response(&self, result: &reqwest::Result<reqwest::Response>, elapsed: Duration) {
+        match result {
+            Ok(response) => {
+                let mut text = format!("← {} in {} ms", response.status(), elapsed.as_millis());
+                for name in TRACED_HEADERS {
+                    if let Some(value) = response.headers().get(*name).and_then(|v| v.to_str().ok()) {
+                        text.push_str(&format!("\n  {}: {}", name, value));
+                    }
+                }
+                self.write(&text);
+            }
+            Err(e) => self.write(&format!("← failed after {} ms: {}", elapsed.as_millis(), e)),
+        }
+    }
+
+    /// A note about what the client is doing, e.g. a round of tool calls.
+    pub fn note(&self, text: &str) {
+        self.write(text);
+    }
+
+    /// Raw response body (level 2).
+    pub fn body(&self, text: &str) {
+        if self.level >= 2 {
+            self.write(&indent(text));
+        }
+    }
+
+    /// One WebSocket message sent (`→`) or received (`←`) in --realtime mode (level 2).
+    pub fn frame(&self, direction: &str, text: &str) {
+        if self.level >= 2 {
+            self.write(&format!("  ws{} {}", direction, text));
+        }
+    }
+
+    /// One raw SSE line (level 2).
+    pub fn sse(&self, line: &str) {
+        if self.level >= 2 {
+            self.write(&format!("  sse: {}", line));
+        }
+    }
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|l| format!("  {}", l)).collect::<Vec<_>>().join("\n")
+}
+
+/// Show only enough of a key to tell keys apart.
+pub fn redact_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() < 12 {
+        return "[redacted]".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("[redacted …{}]", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_shows_a_whole_key() {
+        assert_eq!(redact_key("0123456789abcdef0123456789abcdef"), "[redacted …cdef]");
+        assert_eq!(redact_key("short"), "[redacted]");
+    }
+}
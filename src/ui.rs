@@ -0,0 +1,160 @@
+use std::{
+    future::Future,
+    io::{self, IsTerminal, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::settings::Greeting;
+use crate::stats::thousands;
+
+/// Set while something else prompts on the terminal, so the spinner does not draw over it.
+static SPINNER_PAUSED: AtomicBool = AtomicBool::new(false);
+
+const TITLE: &str = "🤖 Azure OpenAI Chat CLI";
+
+/// Facts about the chat that the banner reports, gathered by the REPL.
+#[derive(Debug, Default)]
+pub struct BannerInfo {
+    pub reasoning: bool,
+    /// "N chunks from <root> (top K per question)".
+    pub rag: Option<String>,
+    /// Session id and number of earlier turns when resuming.
+    pub resumed: Option<(String, usize)>,
+    /// Session id and directory when the chat is saved.
+    pub saved_to: Option<(String, String)>,
+}
+
+/// Startup presentation for interactive chats.
+///
+/// Everything printed before the first prompt goes through here, so scripted and embedded runs,
+/// which never build a `Ui`, start with clean output.
+pub struct Ui {
+    greeting: Greeting,
+}
+
+impl Ui {
+    /// `show_banner` is false for `--no-banner`, which wins over the config.
+    pub fn new(greeting: Greeting, show_banner: bool) -> Self {
+        Self { greeting: Greeting { enabled: greeting.enabled && show_banner, ..greeting } }
+    }
+
+    pub fn banner_lines(&self, info: &BannerInfo) -> Vec<String> {
+        let g = &self.greeting;
+        if !g.enabled {
+            return Vec::new();
+        }
+        let mut lines = vec![g.text.as_deref().unwrap_or(TITLE).trim_end().to_string()];
+        if g.hints {
+            lines.push("Type 'quit' or 'exit' to end the conversation.".to_string());
+            lines.push("Type 'clear' to clear the conversation history.".to_string());
+        }
+        if info.reasoning {
+            lines.push("🧠 Reasoning model: temperature disabled, using max_completion_tokens.".to_string());
+        }
+        if let Some(rag) = &info.rag {
+            lines.push(format!("📚 RAG: {}", rag));
+        }
+        if let Some((id, turns)) = &info.resumed {
+            lines.push(format!("↩️ Resumed session {} ({} earlier turns)", id, turns));
+        }
+        if let Some((id, dir)) = &info.saved_to {
+            lines.push(format!("Session {} will be saved to {}", id, dir));
+        }
+        if !g.separator.is_empty() {
+            lines.push(g.separator.clone());
+        }
+        lines
+    }
+
+    pub fn print_banner(&self, info: &BannerInfo) {
+        for line in self.banner_lines(info) {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Stop spinner updates until the returned guard is dropped, e.g. around a confirmation prompt.
+pub fn pause_spinner() -> SpinnerPause {
+    if !SPINNER_PAUSED.swap(true, Ordering::Relaxed) && io::stderr().is_terminal() {
+        eprint!("\r{}\r", " ".repeat(70));
+    }
+    SpinnerPause
+}
+
+pub struct SpinnerPause;
+
+impl Drop for SpinnerPause {
+    fn drop(&mut self) {
+        SPINNER_PAUSED.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Run `task`, showing `⏳ <label>… 1.2s` on stderr while it runs (when `show` and stderr is a terminal).
+pub async fn with_spinner<T>(label: &str, show: bool, task: impl Future<Output = T>) -> (T, Duration) {
+    let start = Instant::now();
+    let show = show && io::stderr().is_terminal();
+    tokio::pin!(task);
+    let mut ticks = tokio::time::interval(Duration::from_millis(100));
+    let output = loop {
+        tokio::select! {
+            output = &mut task => break output,
+            _ = ticks.tick(), if show => {
+                if !SPINNER_PAUSED.load(Ordering::Relaxed) {
+                    eprint!("\r⏳ {}… {:.1}s ", label, start.elapsed().as_secs_f32());
+                    io::stderr().flush().ok();
+                }
+            }
+        }
+    };
+    if show {
+        eprint!("\r{}\r", " ".repeat(label.chars().count() + 16));
+    }
+    (output, start.elapsed())
+}
+
+/// One line per tool call, e.g. `🔧 files.read_file {"path":"a.txt"} → 1,234 chars in 0.4s`.
+pub fn tool_summary(label: &str, args: &serde_json::Value, elapsed: Duration, result: &serde_json::Value) -> String {
+    let mut args = args.to_string();
+    if args.chars().count() > 60 {
+        args = args.chars().take(59).collect::<String>() + "…";
+    }
+    let outcome = if let Some(error) = result.get("error") {
+        format!("error: {}", error.as_str().map(str::to_string).unwrap_or_else(|| error.to_string()))
+    } else {
+        let size = format!("{} chars", thousands(result.to_string().chars().count() as u64));
+        if result["isError"] == true { format!("tool error, {}", size) } else { size }
+    };
+    format!("🔧 {} {} → {} in {:.1}s", label, args, outcome, elapsed.as_secs_f32())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greeting_controls_the_banner() {
+        let info = BannerInfo { resumed: Some(("abc".to_string(), 2)), ..Default::default() };
+        let default = Ui::new(Greeting::default(), true).banner_lines(&info);
+        assert_eq!(default.first().map(String::as_str), Some(TITLE));
+        assert_eq!(default.len(), 5);
+
+        let custom = Greeting { text: Some("Hello!\n".to_string()), hints: false, separator: String::new(), ..Default::default() };
+        let lines = Ui::new(custom, true).banner_lines(&info);
+        assert_eq!(lines, vec!["Hello!", "↩️ Resumed session abc (2 earlier turns)"]);
+
+        assert!(Ui::new(Greeting::default(), false).banner_lines(&info).is_empty());
+    }
+
+    #[test]
+    fn summarizes_tool_calls() {
+        let args = serde_json::json!({"path": "a.txt"});
+        let ok = serde_json::json!({"content": [{"type": "text", "text": "hi"}]});
+        assert_eq!(
+            tool_summary("files.read_file", &args, Duration::from_millis(400), &ok),
+            "🔧 files.read_file {\"path\":\"a.txt\"} → 41 chars in 0.4s"
+        );
+        let denied = serde_json::json!({"error": "Tool call denied by policy"});
+        assert!(tool_summary("x.y", &args, Duration::ZERO, &denied).ends_with("→ error: Tool call denied by policy in 0.0s"));
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{ModelPrice, Settings};
+
+/// Token usage as reported by the API (`usage` object).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    #[serde(default)]
+    pub completion_tokens: u32,
+    #[serde(default)]
+    pub total_tokens: u32,
+}
+
+impl Usage {
+    pub fn from_json(v: &serde_json::Value) -> Option<Self> {
+        let u = v.get("usage")?;
+        if u.is_null() {
+            return None;
+        }
+        serde_json::from_value(u.clone()).ok()
+    }
+
+    pub fn add(&mut self, other: Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// Built-in context window sizes, matched by longest model-name prefix.
+const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-35-turbo", 16_385),
+    ("gpt-4", 8_192),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("o1", 200_000),
+    ("o1-mini", 128_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    // Other model families deployed through Azure AI Foundry
+    ("meta-llama-3", 8_192),
+    ("meta-llama-3.1", 131_072),
+    ("llama-3.3", 131_072),
+    ("llama-4", 131_072),
+    ("mistral-large", 131_072),
+    ("mistral-small", 32_768),
+    ("phi-3", 4_096),
+    ("phi-3.5", 131_072),
+    ("phi-4", 16_384),
+    ("deepseek-r1", 131_072),
+    ("deepseek-v3", 131_072),
+];
+
+/// Average characters per token by model family, matched by longest model-name prefix. Used where
+/// tokens must be estimated before the service reports usage (the rate limiter). OpenAI's tokenizers,
+/// the default, average about 4 characters per token in English text; these families split text finer.
+const CHARS_PER_TOKEN: &[(&str, f32)] = &[
+    ("meta-llama", 3.5),
+    ("llama", 3.5),
+    ("mistral", 3.2),
+    ("codestral", 3.2),
+    ("ministral", 3.2),
+    ("phi", 3.3),
+    ("deepseek", 3.5),
+];
+
+/// Characters per token assumed for models not in `CHARS_PER_TOKEN` or the config.
+pub const DEFAULT_CHARS_PER_TOKEN: f32 = 4.0;
+
+/// Built-in prices (USD per 1K tokens), matched by longest model-name prefix.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-35-turbo", 0.0005, 0.0015),
+    ("gpt-4", 0.03, 0.06),
+    ("gpt-4-32k", 0.06, 0.12),
+    ("gpt-4-turbo", 0.01, 0.03),
+    ("gpt-4o", 0.0025, 0.01),
+    ("gpt-4o-mini", 0.00015, 0.0006),
+    ("gpt-4.1", 0.002, 0.008),
+    ("o1", 0.015, 0.06),
+    ("o3-mini", 0.0011, 0.0044),
+    ("o4-mini", 0.0011, 0.0044),
+];
+
+fn longest_prefix<'a, T>(model: &str, table: impl Iterator<Item = (&'a str, T)>) -> Option<T> {
+    let model = model.to_lowercase();
+    table
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, v)| v)
+}
+
+pub fn context_window(model: &str, settings: &Settings) -> Option<u32> {
+    if let Some(n) = settings.context_windows.get(model) {
+        return Some(*n);
+    }
+    longest_prefix(model, CONTEXT_WINDOWS.iter().map(|(p, n)| (*p, *n)))
+}
+
+pub fn chars_per_token(model: &str, settings: &Settings) -> f32 {
+    if let Some(n) = settings.chars_per_token.get(model).filter(|n| **n > 0.0) {
+        return *n;
+    }
+    longest_prefix(model, CHARS_PER_TOKEN.iter().map(|(p, n)| (*p, *n))).unwrap_or(DEFAULT_CHARS_PER_TOKEN)
+}
+
+pub fn price(model: &str, settings: &Settings) -> Option<ModelPrice> {
+    if let Some(p) = settings.pricing.get(model) {
+        return Some(*p);
+    }
+    longest_prefix(
+        model,
+        PRICES.iter().map(|(p, input, output)| (*p, ModelPrice { input: *input, output: *output })),
+    )
+}
+
+/// Running usage totals for the current session.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    pub total: Usage,
+    /// Usage of the most recent request; its prompt+completion approximates context in use.
+    pub last: Option<Usage>,
+}
+
+impl UsageTracker {
+    pub fn record(&mut self, usage: Usage) {
+        self.total.add(usage);
+        self.last = Some(usage);
+    }
+
+    pub fn cost(&self, price: Option<ModelPrice>) -> Option<f64> {
+        let p = price?;
+        Some(
+            self.total.prompt_tokens as f64 / 1000.0 * p.input
+                + self.total.completion_tokens as f64 / 1000.0 * p.output,
+        )
+    }
+
+    pub fn context_percent(&self, window: Option<u32>) -> Option<f64> {
+        let last = self.last?;
+        let window = window.filter(|w| *w > 0)?;
+        Some((last.prompt_tokens + last.completion_tokens) as f64 * 100.0 / window as f64)
+    }
+}
+
+/// Render the compact status segment, e.g. `gpt-4o · 12% ctx · $0.0042`.
+pub fn status_segment(model: &str, tracker: &UsageTracker, settings: &Settings) -> Option<String> {
+    let status = &settings.theme.status;
+    if !status.enabled {
+        return None;
+    }
+    let mut parts = Vec::new();
+    if status.model {
+        parts.push(model.to_string());
+    }
+    if status.context {
+        match tracker.context_percent(context_window(model, settings)) {
+            Some(pct) => parts.push(format!("{:.0}% ctx", pct)),
+            None => parts.push("-% ctx".to_string()),
+        }
+    }
+    if status.cost {
+        match tracker.cost(price(model, settings)) {
+            Some(c) => parts.push(format!("${:.4}", c)),
+            None => parts.push("$?".to_string()),
+        }
+    }
+    if parts.is_empty() { None } else { Some(parts.join(" · ")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_longest_prefix() {
+        let settings = Settings::default();
+        assert_eq!(context_window("gpt-4o-mini", &settings), Some(128_000));
+        assert_eq!(context_window("gpt-4-32k", &settings), Some(32_768));
+        assert_eq!(price("gpt-4o-mini-2024", &settings).unwrap().input, 0.00015);
+        assert!(price("my-custom-deployment", &settings).is_none());
+    }
+
+    #[test]
+    fn counts_tokens_per_model_family() {
+        let mut settings = Settings::default();
+        assert_eq!(chars_per_token("gpt-4o", &settings), DEFAULT_CHARS_PER_TOKEN);
+        assert_eq!(chars_per_token("Mistral-Large-2411", &settings), 3.2);
+        assert_eq!(context_window("meta-llama-3.1-70b-instruct", &settings), Some(131_072));
+        settings.chars_per_token.insert("my-llama".into(), 3.0);
+        assert_eq!(chars_per_token("my-llama", &settings), 3.0);
+    }
+
+    #[test]
+    fn renders_status_segment() {
+        let mut settings = Settings::default();
+        settings.theme.status.enabled = true;
+        let mut tracker = UsageTracker::default();
+        tracker.record(Usage { prompt_tokens: 12_000, completion_tokens: 800, total_tokens: 12_800 });
+        assert_eq!(
+            status_segment("gpt-4o", &tracker, &settings).unwrap(),
+            "gpt-4o · 10% ctx · $0.0380"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use dialoguer::{theme::ColorfulTheme, Input, Password, Select};
+use std::path::Path;
+
+use crate::settings::Settings;
+
+/// Interactive first-run setup: asks for endpoint, key, and defaults, then writes the config file.
+/// Returns the updated settings.
+pub fn run(mut settings: Settings, path: &Path) -> Result<Settings> {
+    let theme = ColorfulTheme::default();
+    println!("👋 No Azure OpenAI endpoint/key configured yet. Let's set one up.");
+    println!("   (Settings are written to {})", path.display());
+    println!();
+
+    let endpoint: String = Input::with_theme(&theme)
+        .with_prompt("Endpoint (e.g. https://my-resource.openai.azure.com)")
+        .with_initial_text(settings.endpoint.clone().unwrap_or_default())
+        .validate_with(|s: &String| -> Result<(), &str> {
+            // Checked as it will be saved: surrounding whitespace is dropped
+            let s = s.trim();
+            if s.starts_with("https://") || s.starts_with("http://") {
+                Ok(())
+            } else {
+                Err("The endpoint must start with https:// or http://")
+            }
+        })
+        .interact_text()
+        .context("Failed to read endpoint")?;
+    let endpoint = endpoint.trim().trim_end_matches('/').to_string();
+
+    let api_key = Password::with_theme(&theme)
+        .with_prompt("API key")
+        .interact()
+        .context("Failed to read API key")?;
+
+    let storage = Select::with_theme(&theme)
+        .with_prompt("Where should the API key be stored?")
+        .items(&["System keyring (recommended)", "Config file (plain text)"])
+        .default(0)
+        .interact()
+        .context("Failed to read key storage choice")?;
+
+    let model: String = Input::with_theme(&theme)
+        .with_prompt("Default deployment name")
+        .default(settings.model.clone().unwrap_or_else(|| "gpt-4o".to_string()))
+        .interact_text()
+        .context("Failed to read deployment name")?;
+
+    let api_version: String = Input::with_theme(&theme)
+        .with_prompt("API version")
+        .default(settings.api_version.clone().unwrap_or_else(|| "2025-01-01-preview".to_string()))
+        .interact_text()
+        .context("Failed to read API version")?;
+
+    settings.set_api_key(api_key, storage == 0);
+    settings.endpoint = Some(endpoint);
+    settings.model = Some(model);
+    settings.api_version = Some(api_version);
+
+    settings.save(path)?;
+    println!("✅ Saved {}", path.display());
+    println!();
+    Ok(settings)
+}
@@ -0,0 +1,41 @@
+//! Talks to `mcp fake-server` over stdio the way an MCP host does.
+
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+#[test]
+fn fake_server_speaks_mcp_over_stdio() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust-openai-chat"))
+        .args(["mcp", "fake-server"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to start fake server");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut request = |id: u64, method: &str, params: Value| -> Value {
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        writeln!(stdin, "{}", message).unwrap();
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    };
+
+    let init = request(1, "initialize", json!({"protocolVersion": "2024-11-05", "capabilities": {}}));
+    assert_eq!(init["result"]["serverInfo"]["name"], "fake-server");
+
+    let list = request(2, "tools/list", json!({}));
+    let names: Vec<&str> = list["result"]["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["echo", "add", "fail", "sample", "big", "sleep"]);
+
+    let sum = request(3, "tools/call", json!({"name": "add", "arguments": {"a": 1, "b": 2}}));
+    assert_eq!(sum["id"], 3);
+    assert_eq!(sum["result"]["content"][0]["text"], "3");
+
+    child.kill().ok();
+    child.wait().ok();
+}